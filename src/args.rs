@@ -1,12 +1,33 @@
+use crate::backfill;
+use crate::backup;
+use crate::config;
+use crate::daemon;
+use crate::error::Error;
+use crate::exit::Exit;
+use crate::explain;
+use crate::history;
+use crate::migrations;
+use crate::opml;
+use crate::output::{self, Format, Output};
 use crate::parse;
 use crate::settings;
+use crate::status;
 use crate::tui;
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Parser, Subcommand};
 use rusqlite::Connection;
-use std::{
-    fs::File,
-    io::{prelude::*, BufReader},
-};
+use serde_json::Value as Json;
+use time::OffsetDateTime;
+
+/// Unwraps a result in `args_parser`, or reports the error and returns the
+/// matching exit code.
+macro_rules! or_exit {
+    ($result:expr, $context:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(e) => return report_error($context, e),
+        }
+    };
+}
 
 #[derive(Parser)]
 #[clap(
@@ -14,13 +35,13 @@ use std::{
     version,
     about,
     long_about = None,
-    after_help = "EXAMPLE:\n    nyaadle\n    nyaadle tui\n    nyaadle dl -l https://foo.bar/bar.file"
+    after_help = "EXAMPLE:\n    nyaadle\n    nyaadle tui\n    nyaadle dl -l https://foo.bar/bar.file\n    nyaadle --profile books wle -p\n    nyaadle --db /tmp/test.db set -p"
 )]
-struct Cli {
+pub struct Cli {
     #[clap(
         short,
         long,
-        help = "Parses the RSS Feed normally but does not download anything."
+        help = "Prints what a run would download or skip, and why, without downloading anything."
     )]
     check: bool,
 
@@ -31,6 +52,70 @@ struct Cli {
     )]
     force: bool,
 
+    #[clap(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "Uses the database at PATH instead of the default. Can also be set with NYAADLE_DB."
+    )]
+    pub db: Option<String>,
+
+    #[clap(
+        long,
+        global = true,
+        value_name = "NAME",
+        conflicts_with = "db",
+        help = "Uses a named profile with its own database and log file."
+    )]
+    pub profile: Option<String>,
+
+    #[clap(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "Reads settings from this TOML file instead of the one next to the database. Can also be set with NYAADLE_CONFIG."
+    )]
+    pub config: Option<String>,
+
+    #[clap(
+        long = "setting",
+        global = true,
+        value_name = "KEY=VALUE",
+        help = "Overrides a setting for this run only, e.g. --setting dl-dir=/tmp/dl."
+    )]
+    pub settings: Vec<String>,
+
+    #[clap(
+        short,
+        long,
+        global = true,
+        action = ArgAction::Count,
+        help = "Logs more detail than the log-level setting. Repeat for more."
+    )]
+    pub verbose: u8,
+
+    #[clap(
+        short,
+        long,
+        global = true,
+        action = ArgAction::Count,
+        help = "Logs less than the log-level setting. Repeat for less."
+    )]
+    pub quiet: u8,
+
+    #[clap(
+        long,
+        global = true,
+        value_name = "FORMAT",
+        value_parser = ["table", "json", "tsv", "csv"],
+        default_value = "table",
+        help = "Prints lists and --check results as a table, JSON, TSV or CSV."
+    )]
+    format: String,
+
+    #[clap(long, global = true, help = "Same as --format json.")]
+    json: bool,
+
     #[clap(subcommand)]
     subcommand: Option<Subcommands>,
 }
@@ -100,6 +185,7 @@ enum Subcommands {
             short = 't',
             long = "title",
             value_name = "TITLE",
+            requires = "vid_opt",
             help = "Parses the RSS Feed for the given item. Must be used with `--option`."
         )]
         item: Option<String>,
@@ -107,6 +193,7 @@ enum Subcommands {
         #[clap(
             short = 'o',
             long = "option",
+            requires = "item",
             help = "Used with `--item`. This sets the option value for the item."
         )]
         vid_opt: Option<String>,
@@ -175,8 +262,120 @@ enum Subcommands {
         )]
         get_wbhk: bool,
 
+        #[clap(
+            long = "set-poll-interval",
+            value_name = "MINUTES",
+            help = "Sets how often the daemon checks each feed."
+        )]
+        poll_interval: Option<String>,
+
+        #[clap(long = "get-poll-interval", help = "Returns the daemon poll interval.")]
+        get_poll: bool,
+
+        #[clap(
+            long = "set-fetch-limit",
+            value_name = "COUNT",
+            help = "Sets how many feeds are fetched at the same time."
+        )]
+        fetch_limit: Option<String>,
+
+        #[clap(long = "get-fetch-limit", help = "Returns the concurrent fetch limit.")]
+        get_limit: bool,
+
+        #[clap(
+            long = "set-fetch-timeout",
+            value_name = "SECONDS",
+            help = "Sets how long to wait for a single feed before giving up."
+        )]
+        fetch_timeout: Option<String>,
+
+        #[clap(
+            long = "get-fetch-timeout",
+            help = "Returns the per-feed fetch timeout."
+        )]
+        get_timeout: bool,
+
+        #[clap(
+            long = "set-backup-keep",
+            value_name = "COUNT",
+            help = "Sets how many automatic backups are kept before migrations."
+        )]
+        backup_keep: Option<String>,
+
+        #[clap(
+            long = "get-backup-keep",
+            help = "Returns how many automatic backups are kept."
+        )]
+        get_keep: bool,
+
+        #[clap(
+            long = "set-log-level",
+            value_name = "LEVEL",
+            help = "Sets the log level: off, error, warn, info, debug or trace."
+        )]
+        log_level: Option<String>,
+
+        #[clap(long = "get-log-level", help = "Returns the log level.")]
+        get_level: bool,
+
+        #[clap(
+            long = "set-log-format",
+            value_name = "FORMAT",
+            help = "Sets the log file format: text, or json for one JSON object per line."
+        )]
+        log_format: Option<String>,
+
+        #[clap(long = "get-log-format", help = "Returns the log file format.")]
+        get_format: bool,
+
+        #[clap(
+            long = "set-log-keep-days",
+            value_name = "DAYS",
+            help = "Sets how many days of entries the log table keeps. 0 keeps everything."
+        )]
+        log_keep_days: Option<String>,
+
+        #[clap(
+            long = "get-log-keep-days",
+            help = "Returns how many days of log entries are kept."
+        )]
+        get_days: bool,
+
+        #[clap(
+            long = "set-log-keep-rows",
+            value_name = "COUNT",
+            help = "Sets how many entries the log table keeps. 0 means no limit."
+        )]
+        log_keep_rows: Option<String>,
+
+        #[clap(
+            long = "get-log-keep-rows",
+            help = "Returns how many log entries are kept."
+        )]
+        get_rows: bool,
+
+        #[clap(
+            long = "set-log-max-size",
+            value_name = "MB",
+            help = "Sets the size the log file is rotated at. 0 never rotates."
+        )]
+        log_max_size: Option<String>,
+
+        #[clap(
+            long = "get-log-max-size",
+            help = "Returns the size the log file is rotated at."
+        )]
+        get_size: bool,
+
         #[clap(long = "get-db-ver", help = "Returns the Database version.")]
         get_ver: bool,
+
+        #[clap(
+            long,
+            requires = "print",
+            help = "Used with `--print`. Shows where each setting's value comes from."
+        )]
+        origin: bool,
     },
 
     #[clap(
@@ -225,7 +424,11 @@ enum Subcommands {
         print: bool,
     },
 
-    #[clap(visible_alias = "fds", about = "Subcommand to configure RSS Feeds.")]
+    #[clap(
+        visible_alias = "fds",
+        about = "Subcommand to configure RSS Feeds.",
+        after_help = "ALIAS:\n     fds\n\nEXAMPLE:\n    nyaadle fds -a -n Books -u https://foo.com/bar.rss\n    nyaadle fds -a -n Jackett -k torznab -u http://localhost:9117/api/v2.0/indexers/all/results/torznab/api --api-key KEY --categories 5070\n    nyaadle fds -a -n Search -k search -u \"https://nyaa.si/?page=rss&q={query}&c=1_2\"\n    nyaadle fds --export-opml feeds.opml\n    nyaadle fds --import-opml feeds.opml --on-conflict rename"
+    )]
     Feeds {
         #[clap(short, long, help = "Add a new feed.")]
         add: bool,
@@ -242,10 +445,28 @@ enum Subcommands {
         #[clap(long = "set-default", help = "Set a feed as the global default feed.")]
         set_default: bool,
 
-        #[clap(short, long, help = "Print active feed names and URLs.")]
+        #[clap(long, help = "Stop checking a feed without deleting it.")]
+        disable: bool,
+
+        #[clap(
+            long,
+            conflicts_with = "disable",
+            help = "Resume checking a disabled feed."
+        )]
+        enable: bool,
+
+        #[clap(
+            short,
+            long,
+            help = "Minutes to wait between checks of a feed. 0 checks it on every run.",
+            value_name = "MINUTES"
+        )]
+        interval: Option<i64>,
+
+        #[clap(short, long, help = "Print the enabled feeds.")]
         print: bool,
 
-        #[clap(long, help = "Print all columns including IDs.")]
+        #[clap(long, help = "Include disabled feeds when printing.")]
         all: bool,
 
         #[clap(short, long, help = "Name of the feed.", value_name = "NAME")]
@@ -260,16 +481,277 @@ enum Subcommands {
 
         #[clap(short, long, help = "URL of the RSS feed.", value_name = "URL")]
         url: Option<String>,
+
+        #[clap(
+            short,
+            long,
+            help = "Type of feed: a plain RSS/Atom URL, a Torznab API endpoint, or a search URL containing {query}.",
+            value_name = "KIND",
+            value_parser = ["rss", "torznab", "search"]
+        )]
+        kind: Option<String>,
+
+        #[clap(
+            long = "api-key",
+            help = "API key for a Torznab feed.",
+            value_name = "KEY"
+        )]
+        api_key: Option<String>,
+
+        #[clap(
+            long,
+            help = "Comma-separated Torznab category IDs to search.",
+            value_name = "IDS"
+        )]
+        categories: Option<String>,
+
+        #[clap(
+            long = "import-opml",
+            help = "Add the feeds listed in an OPML file.",
+            value_name = "FILE"
+        )]
+        import_opml: Option<String>,
+
+        #[clap(
+            long = "export-opml",
+            help = "Write all feeds to an OPML file.",
+            value_name = "FILE"
+        )]
+        export_opml: Option<String>,
+
+        #[clap(
+            long = "on-conflict",
+            help = "What to do when an imported feed's name is already taken.",
+            value_name = "ACTION",
+            value_parser = ["skip", "rename", "replace"],
+            default_value = "skip"
+        )]
+        on_conflict: String,
     },
     #[clap(about = "Opens the log viewer.")]
     Log,
+
+    #[clap(
+        about = "Keeps running and checks each feed on a schedule.",
+        after_help = "EXAMPLE:\n    nyaadle daemon\n    nyaadle daemon -i 15"
+    )]
+    Daemon {
+        #[clap(
+            short,
+            long,
+            value_name = "MINUTES",
+            help = "Overrides the poll interval set in the database."
+        )]
+        interval: Option<u64>,
+    },
+
+    #[clap(
+        about = "Looks back through a feed for episodes released before an item was added.",
+        after_help = "EXAMPLE:\n    nyaadle backfill -i 3\n    nyaadle backfill -i 3 -p 10 -s 1-5,8\n    nyaadle backfill -i 3 --since 2024-10-01 -s all\n    nyaadle -c backfill -i 3",
+        arg_required_else_help = true
+    )]
+    Backfill {
+        #[clap(
+            short,
+            long,
+            help = "ID of the watch-list item to look back for.",
+            value_name = "ID"
+        )]
+        item: i32,
+
+        #[clap(
+            short,
+            long,
+            default_value_t = 5,
+            help = "Maximum number of result pages to walk through.",
+            value_name = "PAGES"
+        )]
+        pages: u32,
+
+        #[clap(
+            long,
+            help = "Only list episodes published on or after this date.",
+            value_name = "YYYY-MM-DD"
+        )]
+        since: Option<String>,
+
+        #[clap(
+            short,
+            long,
+            help = "Episodes to download, e.g. `1-5,8` or `all`. Prompts if not given.",
+            value_name = "RANGE"
+        )]
+        select: Option<String>,
+    },
+
+    #[clap(
+        about = "Lists past downloads, newest first.",
+        after_help = "EXAMPLE:\n    nyaadle history\n    nyaadle history --since 7d --feed Default\n    nyaadle history --item 3 --format csv\n    nyaadle history --grep \"- 07\" --format json"
+    )]
+    History {
+        #[clap(
+            long,
+            help = "Only downloads since a date, or a time ago such as 12h, 7d or 2w.",
+            value_name = "YYYY-MM-DD|AGO"
+        )]
+        since: Option<String>,
+
+        #[clap(long, help = "Only downloads from this feed.", value_name = "NAME")]
+        feed: Option<String>,
+
+        #[clap(
+            short,
+            long,
+            help = "Only downloads matched by this watch-list ID, or by items whose title contains this.",
+            value_name = "ID|TITLE"
+        )]
+        item: Option<String>,
+
+        #[clap(
+            long,
+            help = "Only downloads whose title contains this.",
+            value_name = "TEXT"
+        )]
+        grep: Option<String>,
+
+        #[clap(
+            short = 'n',
+            long,
+            help = "Shows at most this many downloads.",
+            value_name = "N"
+        )]
+        limit: Option<usize>,
+    },
+
+    #[clap(
+        about = "Shows which watch-list entries would download a release title, and why.",
+        after_help = "EXAMPLE:\n    nyaadle explain \"[SubsPlease] Sousou no Frieren - 07 (1080p).mkv\"\n    nyaadle explain \"Spy x Family v01 (Digital)\" --json"
+    )]
+    Explain {
+        #[clap(help = "The release title, as it appears in the feed.")]
+        title: String,
+    },
+
+    #[clap(
+        about = "Exports or imports settings, feeds and the watch-list as TOML.",
+        after_help = "EXAMPLE:\n    nyaadle config export nyaadle.toml\n    nyaadle config import nyaadle.toml --dry-run\n    nyaadle config import nyaadle.toml --prune",
+        arg_required_else_help = true
+    )]
+    Config {
+        #[clap(subcommand)]
+        action: ConfigAction,
+    },
+
+    #[clap(
+        about = "Maintains the database.",
+        after_help = "EXAMPLE:\n    nyaadle db backup\n    nyaadle db backup ~/nyaadle-copy.db\n    nyaadle db restore ~/nyaadle-copy.db\n    nyaadle db migrate --dry-run",
+        arg_required_else_help = true
+    )]
+    Db {
+        #[clap(subcommand)]
+        action: DbAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DbAction {
+    #[clap(about = "Applies pending schema migrations.")]
+    Migrate {
+        #[clap(
+            long = "dry-run",
+            help = "Show what each migration would change without applying it."
+        )]
+        dry_run: bool,
+    },
+
+    #[clap(about = "Copies the database, even while another nyaadle is using it.")]
+    Backup {
+        #[clap(
+            value_name = "PATH",
+            help = "Where to write the backup. Defaults to the backups folder next to the database."
+        )]
+        path: Option<String>,
+    },
+
+    #[clap(about = "Replaces the database with a backup.")]
+    Restore {
+        #[clap(value_name = "PATH")]
+        path: String,
+    },
+}
+
+impl Cli {
+    /// Returns true if the command applies migrations itself, so main
+    /// shouldn't run them on startup.
+    pub fn runs_own_migrations(&self) -> bool {
+        matches!(
+            self.subcommand,
+            Some(Subcommands::Db {
+                action: DbAction::Migrate { .. }
+            })
+        )
+    }
+
+    /// Returns the format picked with `--format` or `--json`.
+    pub fn output_format(&self) -> Format {
+        if self.json {
+            return Format::Json;
+        }
+        Format::parse(&self.format).unwrap_or(Format::Table)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    #[clap(about = "Writes the configuration to a TOML file, or to stdout.")]
+    Export {
+        #[clap(value_name = "FILE")]
+        file: Option<String>,
+    },
+
+    #[clap(about = "Applies a TOML configuration file to the database.")]
+    Import {
+        #[clap(value_name = "FILE")]
+        file: String,
+
+        #[clap(long = "dry-run", help = "Show what would change without applying it.")]
+        dry_run: bool,
+
+        #[clap(
+            long,
+            help = "Also remove feeds and watch-list items missing from the file."
+        )]
+        prune: bool,
+    },
 }
 
-pub async fn args_parser(conn: &Connection) {
-    let args = Cli::parse();
+/// Settings shown by `settings --print`, in order.
+const PRINTED_SETTINGS: &[&str] = &[
+    "dl-dir",
+    "ar-dir",
+    "url",
+    "log",
+    "webhk_url",
+    "poll-interval",
+    "fetch-limit",
+    "fetch-timeout",
+    "backup-keep",
+    "log-level",
+    "log-format",
+    "log-keep-days",
+    "log-keep-rows",
+    "log-max-size",
+    "db-ver",
+];
+
+/// Runs the command given on the command line and returns how it ended.
+pub async fn args_parser(conn: &Connection, args: Cli) -> Exit {
+    let format = args.output_format();
+    output::set_format(format);
+    let mut exit = Exit::Ok;
 
     if args.force {
-        println!("Forcing downloads.");
+        status!("Forcing downloads.");
     }
 
     match args.subcommand {
@@ -281,11 +763,11 @@ pub async fn args_parser(conn: &Connection) {
             feeds,
             log,
         }) => {
-            if settings && !watchlist && !feeds && !log{
+            if settings && !watchlist && !feeds && !log {
                 tui::arg_tui("set");
-            } else if !settings && watchlist && !feeds && !log{
+            } else if !settings && watchlist && !feeds && !log {
                 tui::arg_tui("wle");
-            } else if !settings && !watchlist && feeds && !log{
+            } else if !settings && !watchlist && feeds && !log {
                 tui::arg_tui("fds");
             } else if !settings && !watchlist && !feeds && log {
                 tui::arg_tui("log");
@@ -294,19 +776,48 @@ pub async fn args_parser(conn: &Connection) {
             }
         }
 
-        Some(Subcommands::Download { links, file }) => {
-            if let Some(urls) = links {
-                parse::arg_dl(conn, urls).await.unwrap();
+        Some(Subcommands::Explain { title }) => match explain::explain(conn, &title) {
+            Ok(explanations) if explanations.is_empty() && format == Format::Table => {
+                println!("The watch-list is empty.");
             }
+            Ok(explanations) => {
+                print!("{}", explain::render(&explanations, format));
+                if format == Format::Table {
+                    let matched: Vec<String> = explanations
+                        .iter()
+                        .filter(|e| e.matched())
+                        .map(|e| e.entry.id.to_string())
+                        .collect();
+                    if matched.is_empty() {
+                        println!("\nNo watch-list entry would download this title.");
+                    } else {
+                        let noun = if matched.len() == 1 {
+                            "entry"
+                        } else {
+                            "entries"
+                        };
+                        println!("\nWould be downloaded by {} {}.", noun, matched.join(", "));
+                    }
+                }
+            }
+            Err(e) => exit = report_error("Failed to explain the title", e),
+        },
+
+        Some(Subcommands::Download { links, file }) => {
+            let mut urls = links.unwrap_or_default();
             if let Some(name) = file {
-                let filename = File::open(name).expect("Failed to open file.");
-                let buf = BufReader::new(filename);
-                let links: Vec<String> = buf
-                    .lines()
-                    .map(|l| l.expect("Failed to read line"))
-                    .collect();
-                parse::arg_dl(conn, links).await.unwrap();
+                match std::fs::read_to_string(&name) {
+                    Ok(contents) => urls.extend(contents.lines().map(String::from)),
+                    Err(e) => {
+                        status!("Failed to read {}: {}", name, e);
+                        return Exit::Usage;
+                    }
+                }
             }
+            exit = match parse::arg_dl(conn, urls).await {
+                Ok(summary) => Exit::from_summary(&summary),
+                Err(e) => report_error("Download failed", e),
+            };
         }
 
         Some(Subcommands::Parse {
@@ -314,9 +825,10 @@ pub async fn args_parser(conn: &Connection) {
             item,
             vid_opt,
         }) => {
-            parse::arg_parse(conn, args.force, feed, item, vid_opt)
-                .await
-                .unwrap();
+            exit = match parse::arg_parse(conn, args.force, feed, item, vid_opt).await {
+                Ok(summary) => Exit::from_summary(&summary),
+                Err(e) => report_error("Parse failed", e),
+            };
         }
 
         Some(Subcommands::Settings {
@@ -330,45 +842,110 @@ pub async fn args_parser(conn: &Connection) {
             get_url,
             get_log,
             get_wbhk,
+            poll_interval,
+            get_poll,
+            fetch_limit,
+            get_limit,
+            fetch_timeout,
+            get_timeout,
+            backup_keep,
+            get_keep,
+            log_level,
+            get_level,
+            log_format,
+            get_format,
+            log_keep_days,
+            get_days,
+            log_keep_rows,
+            get_rows,
+            log_max_size,
+            get_size,
             print,
             get_ver,
+            origin,
         }) => {
             if let Some(dl) = dl_dir {
-                settings::arg_set(conn, "dl-dir", &dl);
+                exit = set_setting(conn, "dl-dir", &dl);
             } else if let Some(ar) = ar_dir {
-                settings::arg_set(conn, "ar-dir", &ar);
+                exit = set_setting(conn, "ar-dir", &ar);
             } else if let Some(url) = url {
-                settings::arg_set(conn, "url", &url);
+                exit = set_setting(conn, "url", &url);
             } else if let Some(log) = log {
-                settings::arg_set(conn, "log", &log);
+                exit = set_setting(conn, "log", &log);
             } else if let Some(webhk_url) = webhk_url {
-                settings::arg_set(conn, "webhk_url", &webhk_url);
-            } else if get_dl || get_ar || get_url || get_log || print || get_ver || get_wbhk {
-                if get_dl {
-                    settings::arg_get_set(conn, "dl-dir");
-                }
-                if get_ar {
-                    settings::arg_get_set(conn, "ar-dir");
-                }
-                if get_url {
-                    settings::arg_get_set(conn, "url");
-                }
-                if get_log {
-                    settings::arg_get_set(conn, "log");
-                }
-                if get_wbhk {
-                    settings::arg_get_set(conn, "webhk_url");
-                }
-                if get_ver {
-                    settings::arg_get_set(conn, "db-ver");
-                }
-                if print {
-                    settings::arg_get_set(conn, "dl-dir");
-                    settings::arg_get_set(conn, "ar-dir");
-                    settings::arg_get_set(conn, "url");
-                    settings::arg_get_set(conn, "log");
-                    settings::arg_get_set(conn, "webhk_url");
-                    settings::arg_get_set(conn, "db-ver");
+                exit = set_setting(conn, "webhk_url", &webhk_url);
+            } else if let Some(poll_interval) = poll_interval {
+                exit = set_setting(conn, "poll-interval", &poll_interval);
+            } else if let Some(fetch_limit) = fetch_limit {
+                exit = set_setting(conn, "fetch-limit", &fetch_limit);
+            } else if let Some(fetch_timeout) = fetch_timeout {
+                exit = set_setting(conn, "fetch-timeout", &fetch_timeout);
+            } else if let Some(backup_keep) = backup_keep {
+                exit = set_setting(conn, "backup-keep", &backup_keep);
+            } else if let Some(log_level) = log_level {
+                exit = set_setting(conn, "log-level", &log_level);
+            } else if let Some(log_format) = log_format {
+                exit = set_setting(conn, "log-format", &log_format);
+            } else if let Some(log_keep_days) = log_keep_days {
+                exit = set_setting(conn, "log-keep-days", &log_keep_days);
+            } else if let Some(log_keep_rows) = log_keep_rows {
+                exit = set_setting(conn, "log-keep-rows", &log_keep_rows);
+            } else if let Some(log_max_size) = log_max_size {
+                exit = set_setting(conn, "log-max-size", &log_max_size);
+            } else if get_dl
+                || get_ar
+                || get_url
+                || get_log
+                || print
+                || get_ver
+                || get_wbhk
+                || get_poll
+                || get_limit
+                || get_timeout
+                || get_keep
+                || get_level
+                || get_format
+                || get_days
+                || get_rows
+                || get_size
+            {
+                let requested = [
+                    (get_dl, "dl-dir"),
+                    (get_ar, "ar-dir"),
+                    (get_url, "url"),
+                    (get_log, "log"),
+                    (get_wbhk, "webhk_url"),
+                    (get_poll, "poll-interval"),
+                    (get_limit, "fetch-limit"),
+                    (get_timeout, "fetch-timeout"),
+                    (get_keep, "backup-keep"),
+                    (get_level, "log-level"),
+                    (get_format, "log-format"),
+                    (get_days, "log-keep-days"),
+                    (get_rows, "log-keep-rows"),
+                    (get_size, "log-max-size"),
+                    (get_ver, "db-ver"),
+                ];
+                let mut keys: Vec<&str> = requested
+                    .iter()
+                    .filter_map(|(wanted, key)| wanted.then_some(*key))
+                    .collect();
+                if format != Format::Table {
+                    if print {
+                        keys.extend(PRINTED_SETTINGS);
+                    }
+                    print!("{}", settings_output(conn, &keys).render(format));
+                } else {
+                    for key in keys {
+                        settings::arg_get_set(conn, key);
+                    }
+                    if print && origin {
+                        settings::print_origins(conn);
+                    } else if print {
+                        for key in PRINTED_SETTINGS {
+                            settings::arg_get_set(conn, key);
+                        }
+                    }
                 }
             } else {
                 tui::arg_tui("set");
@@ -381,62 +958,194 @@ pub async fn args_parser(conn: &Connection) {
             rename,
             delete,
             set_default,
+            disable,
+            enable,
+            interval,
             print,
             all,
             name,
             new_name,
             url,
+            kind,
+            api_key,
+            categories,
+            import_opml,
+            export_opml,
+            on_conflict,
         }) => {
-            if add {
-                let n = name.expect("Feed name is required to add a feed.");
-                let u = url.expect("Feed URL is required to add a feed.");
-                settings::db_write_feed(conn, &n, &u, false).expect("Failed to save feed.");
+            let kind = kind.as_deref().and_then(settings::FeedKind::parse);
+            if let Some(path) = import_opml {
+                let on_conflict =
+                    opml::Conflict::parse(&on_conflict).unwrap_or(opml::Conflict::Skip);
+                match opml::import(conn, &path, on_conflict) {
+                    Ok(count) => println!("Imported {} feeds from {}.", count, path),
+                    Err(e) => {
+                        println!("Failed to import {}: {}", path, e);
+                        exit = Exit::from_error(&e);
+                    }
+                }
+            } else if let Some(path) = export_opml {
+                match opml::export(conn, &path) {
+                    Ok(count) => println!("Exported {} feeds to {}.", count, path),
+                    Err(e) => {
+                        println!("Failed to export {}: {}", path, e);
+                        exit = Exit::from_error(&e);
+                    }
+                }
+            } else if add {
+                let (n, u) = match (name, url) {
+                    (Some(n), Some(u)) => (n, u),
+                    _ => {
+                        println!("Please provide both a feed name and a URL to add a feed.");
+                        return Exit::Usage;
+                    }
+                };
+                if kind == Some(settings::FeedKind::Search) && !u.contains(parse::QUERY_PLACEHOLDER)
+                {
+                    println!(
+                        "Search feed URLs must contain {} where the title goes.",
+                        parse::QUERY_PLACEHOLDER
+                    );
+                    return Exit::Usage;
+                }
+                or_exit!(
+                    settings::db_write_feed(conn, &n, &u, false),
+                    "Failed to save feed"
+                );
+                if kind.is_some() || api_key.is_some() || categories.is_some() {
+                    or_exit!(
+                        settings::set_feed_source(
+                            conn,
+                            &n,
+                            kind.unwrap_or(settings::FeedKind::Rss),
+                            api_key.as_deref(),
+                            categories.as_deref(),
+                        ),
+                        "Failed to save feed"
+                    );
+                }
                 println!("Successfully added feed \"{}\".", n);
             } else if edit {
-                let n = name.expect("Feed name is required to edit a feed.");
-                let u = url.expect("New feed URL is required.");
-                settings::update_feed_url(conn, &n, &u).expect("Failed to update feed URL.");
-                println!("Updated feed \"{}\" with new URL.", n);
+                let Some(n) = feed_name(name, "edit") else {
+                    return Exit::Usage;
+                };
+                if url.is_none() && kind.is_none() && api_key.is_none() && categories.is_none() {
+                    println!("Provide a new --url, --kind, --api-key or --categories.");
+                    return Exit::Usage;
+                }
+                let feeds = or_exit!(settings::read_feeds(conn), "Failed to read feeds");
+                let Some(current) = find_feed(&feeds, &n) else {
+                    return Exit::Usage;
+                };
+                let new_url = url.as_deref().unwrap_or(&current.url);
+                if kind.unwrap_or(current.kind) == settings::FeedKind::Search
+                    && !new_url.contains(parse::QUERY_PLACEHOLDER)
+                {
+                    println!(
+                        "Search feed URLs must contain {} where the title goes.",
+                        parse::QUERY_PLACEHOLDER
+                    );
+                    return Exit::Usage;
+                }
+                if let Some(u) = &url {
+                    or_exit!(
+                        settings::update_feed_url(conn, &n, u),
+                        "Failed to update feed URL"
+                    );
+                    println!("Updated feed \"{}\" with new URL.", n);
+                }
+                if kind.is_some() || api_key.is_some() || categories.is_some() {
+                    or_exit!(
+                        settings::set_feed_source(
+                            conn,
+                            &n,
+                            kind.unwrap_or(current.kind),
+                            api_key.as_deref().or(current.api_key.as_deref()),
+                            categories.as_deref().or(current.categories.as_deref()),
+                        ),
+                        "Failed to update feed"
+                    );
+                    println!("Updated source settings for feed \"{}\".", n);
+                }
             } else if rename {
-                let n = name.expect("Current feed name is required.");
-                let nn = new_name.expect("New feed name is required via --new-name.");
-                settings::rename_feed(conn, &n, &nn).expect("Failed to rename feed.");
+                let Some(n) = feed_name(name, "rename") else {
+                    return Exit::Usage;
+                };
+                let Some(nn) = new_name else {
+                    println!("Please provide the feed's new name with --new-name.");
+                    return Exit::Usage;
+                };
+                or_exit!(
+                    settings::rename_feed(conn, &n, &nn),
+                    "Failed to rename feed"
+                );
                 println!("Renamed feed \"{}\" to \"{}\".", n, nn);
             } else if set_default {
-                let n = name.expect("Feed name is required to set default.");
-                settings::set_default_feed(conn, &n).expect("Failed to set default feed.");
+                let Some(n) = feed_name(name, "make the default") else {
+                    return Exit::Usage;
+                };
+                or_exit!(
+                    settings::set_default_feed(conn, &n),
+                    "Failed to set default feed"
+                );
                 println!("\"{}\" is now the default feed.", n);
+            } else if disable || enable || interval.is_some() {
+                let Some(n) = feed_name(name, "change") else {
+                    return Exit::Usage;
+                };
+                if disable || enable {
+                    match or_exit!(
+                        settings::set_feed_enabled(conn, &n, enable),
+                        "Failed to update feed"
+                    ) {
+                        0 => println!("Feed \"{}\" not found.", n),
+                        _ if enable => println!("Enabled feed \"{}\".", n),
+                        _ => println!("Disabled feed \"{}\".", n),
+                    }
+                }
+                if let Some(minutes) = interval {
+                    if minutes < 0 {
+                        println!("Interval must be 0 or a positive number of minutes.");
+                        return Exit::Usage;
+                    }
+                    match or_exit!(
+                        settings::set_feed_interval(conn, &n, minutes),
+                        "Failed to update feed"
+                    ) {
+                        0 => println!("Feed \"{}\" not found.", n),
+                        _ => println!("Feed \"{}\" will be checked every {} minutes.", n, minutes),
+                    }
+                }
             } else if delete {
-                let n = name.expect("Feed name is required for deletion.");
-                let feeds = settings::read_feeds(conn).expect("Failed to read feeds.");
+                let Some(n) = feed_name(name, "delete") else {
+                    return Exit::Usage;
+                };
+                let feeds = or_exit!(settings::read_feeds(conn), "Failed to read feeds");
 
                 if feeds.len() <= 1 {
                     println!("Error: Refusing deletion. Cannot delete the last remaining feed.");
-                    return;
+                    return Exit::Usage;
                 }
 
-                let target_feed = feeds
-                    .iter()
-                    .find(|f| f.name == n)
-                    .expect("Specified feed not found.");
+                let Some(target_feed) = find_feed(&feeds, &n) else {
+                    return Exit::Usage;
+                };
 
                 let mut replacement_default_name = None;
                 if target_feed.is_default {
                     println!("'{}' is currently the default feed.", n);
                     println!("Enter the name of the replacement default feed:");
-                    let mut input = String::new();
-                    std::io::stdin().read_line(&mut input).unwrap();
+                    let input = or_exit!(read_line(), "Failed to read the answer");
                     let def_input = input.trim().to_string();
                     if !feeds.iter().any(|f| f.name == def_input && f.name != n) {
                         println!("Invalid replacement feed chosen. Aborting.");
-                        return;
+                        return Exit::Usage;
                     }
                     replacement_default_name = Some(def_input);
                 }
 
                 println!("Enter feed name to reassign dependent watchlist items (leave empty to delete items):");
-                let mut input = String::new();
-                std::io::stdin().read_line(&mut input).unwrap();
+                let input = or_exit!(read_line(), "Failed to read the answer");
                 let reassign_input = input.trim();
                 let reassign_name = if !reassign_input.is_empty() {
                     if !feeds
@@ -444,40 +1153,27 @@ pub async fn args_parser(conn: &Connection) {
                         .any(|f| f.name == reassign_input && f.name != n)
                     {
                         println!("Invalid reassignment feed chosen. Aborting.");
-                        return;
+                        return Exit::Usage;
                     }
                     Some(reassign_input.to_string())
                 } else {
                     None
                 };
 
-                settings::db_delete_feed(
-                    conn,
-                    &n,
-                    replacement_default_name.as_deref(),
-                    reassign_name.as_deref(),
-                )
-                .expect("Failed to delete feed securely.");
+                or_exit!(
+                    settings::db_delete_feed(
+                        conn,
+                        &n,
+                        replacement_default_name.as_deref(),
+                        reassign_name.as_deref(),
+                    ),
+                    "Failed to delete feed"
+                );
                 println!("Feed '{}' successfully purged.", n);
             } else if print {
-                let feeds = settings::read_feeds(conn).expect("Failed to read feeds.");
-                if all {
-                    println!("ID | Default | Feed Name | URL");
-                    for f in feeds {
-                        let def_marker = if f.is_default { "*" } else { " " };
-                        println!("{} |    {}    | {} | {}", f.id, def_marker, f.name, f.url);
-                    }
-                } else {
-                    println!("Feed Name | URL");
-                    for f in feeds {
-                        let display_name = if f.is_default {
-                            format!("{} [default]", f.name)
-                        } else {
-                            f.name.clone()
-                        };
-                        println!("{} | {}", display_name, f.url);
-                    }
-                }
+                let feeds = or_exit!(settings::read_feeds(conn), "Failed to read feeds");
+                let feeds = feeds.into_iter().filter(|f| all || f.enabled);
+                print!("{}", feeds_output(feeds).render(format));
             } else {
                 tui::arg_tui("fds");
             }
@@ -494,43 +1190,52 @@ pub async fn args_parser(conn: &Connection) {
             print,
         }) => {
             if add && (!delete || !edit || !print) {
-                let tgt = item_builder(value, option);
-                let feed_id = if let Some(f_name) = feed {
-                    let feeds = settings::read_feeds(conn).expect("Failed to read feeds.");
-                    feeds
-                        .iter()
-                        .find(|f| f.name == f_name)
-                        .map(|f| f.id)
-                        .unwrap_or_else(|| {
-                            println!(
-                                "Feed '{}' not found! Falling back to system default.",
-                                f_name
-                            );
-                            settings::get_default_feed_id(conn)
-                                .expect("No default feed configured.")
-                        })
-                } else {
-                    settings::get_default_feed_id(conn)
-                        .expect("No default feed set. Run 'nyaadle feeds --add' first.")
+                let Some(tgt) = item_builder(value, option) else {
+                    return Exit::Usage;
+                };
+                let feeds = or_exit!(settings::read_feeds(conn), "Failed to read feeds");
+                let named = feed.and_then(|f_name| {
+                    let found = feeds.iter().find(|f| f.name == f_name).map(|f| f.id);
+                    if found.is_none() {
+                        println!(
+                            "Feed '{}' not found! Falling back to system default.",
+                            f_name
+                        );
+                    }
+                    found
+                });
+                let Some(feed_id) =
+                    named.or_else(|| feeds.iter().find(|f| f.is_default).map(|f| f.id))
+                else {
+                    println!("No default feed set. Run 'nyaadle feeds --add' first.");
+                    return Exit::Usage;
                 };
-                settings::db_write_wl(conn, &tgt.0, &tgt.1, feed_id)
-                    .expect("Failed to write to the database.");
+                or_exit!(
+                    settings::db_write_wl(conn, &tgt.0, &tgt.1, feed_id),
+                    "Failed to add the item"
+                );
                 println!("Added \"{} | {}\" to the watchlist.", &tgt.0, &tgt.1);
             } else if edit && (!add || !delete || !print) {
-                let tgt = item_builder(value, option);
+                let Some(tgt) = item_builder(value, option) else {
+                    return Exit::Usage;
+                };
                 if let Some(ids) = item {
+                    let feeds = or_exit!(settings::read_feeds(conn), "Failed to read feeds");
                     for id in ids {
                         if let Some(f_name) = &feed {
-                            let feeds = settings::read_feeds(conn).expect("Failed to read feeds.");
                             if let Some(f) = feeds.iter().find(|f| f.name == *f_name) {
-                                settings::update_wl_with_feed(conn, &tgt.0, &tgt.1, f.id, &id)
-                                    .expect("Failed to update watchlist item feed relation.");
+                                or_exit!(
+                                    settings::update_wl_with_feed(conn, &tgt.0, &tgt.1, f.id, &id),
+                                    "Failed to update the item"
+                                );
                             } else {
                                 println!("Feed '{}' not found. Skipping relation shift.", f_name);
                             }
                         } else {
-                            settings::update_wl(conn, &tgt.0, &tgt.1, &id)
-                                .expect("Failed to write to the database.");
+                            or_exit!(
+                                settings::update_wl(conn, &tgt.0, &tgt.1, &id),
+                                "Failed to update the item"
+                            );
                         }
                         println!("Updated {} to \"{} | {}\".", id, &tgt.0, &tgt.1);
                     }
@@ -538,15 +1243,26 @@ pub async fn args_parser(conn: &Connection) {
             } else if delete && (!add || !edit || !print) {
                 if let Some(ids) = item {
                     for id in ids {
-                        settings::db_delete_wl(conn, &id).expect("Failed to delete item");
+                        or_exit!(
+                            settings::db_delete_wl(conn, &id),
+                            "Failed to delete the item"
+                        );
                         println!("Item deleted.");
                     }
                 }
             } else if print && (!add || !edit || !delete) {
-                let wl = settings::read_watch_list(conn).expect("Failed to unpack watchlist.");
-                let feeds = settings::read_feeds(conn).expect("Failed to read feeds.");
+                let wl = or_exit!(
+                    settings::read_watch_list(conn),
+                    "Failed to read the watch-list"
+                );
+                let feeds = or_exit!(settings::read_feeds(conn), "Failed to read feeds");
 
-                println!("ID | Item Title | Option | Feed Name");
+                let mut out = Output::new(&[
+                    ("id", "ID"),
+                    ("title", "Item Title"),
+                    ("option", "Option"),
+                    ("feed", "Feed Name"),
+                ]);
                 for item in wl {
                     let feed_name = feeds
                         .iter()
@@ -554,11 +1270,14 @@ pub async fn args_parser(conn: &Connection) {
                         .map(|f| f.name.as_str())
                         .unwrap_or("Unknown");
 
-                    println!(
-                        "{} | {} | {} | {}",
-                        item.id, item.title, item.option, feed_name
-                    );
+                    out.row(vec![
+                        Json::from(item.id),
+                        Json::from(item.title),
+                        Json::from(item.option),
+                        Json::from(feed_name),
+                    ]);
                 }
+                print!("{}", out.render(format));
             } else {
                 tui::arg_tui("wle");
             }
@@ -566,34 +1285,299 @@ pub async fn args_parser(conn: &Connection) {
 
         None => {
             if args.check {
-                parse::feed_parser(conn, args.check, args.force, None, None, None)
-                    .await
-                    .unwrap();
+                let checked =
+                    parse::feed_parser(conn, args.check, args.force, None, None, None).await;
+                exit = match checked {
+                    Ok(summary) => {
+                        if summary.found.is_empty() && format == Format::Table {
+                            println!("No matching items found.");
+                        } else {
+                            print!("{}", plan_output(&summary.found).render(format));
+                        }
+                        Exit::from_summary(&summary)
+                    }
+                    Err(e) => report_error("Check failed", e),
+                };
             } else {
-                default_logic(conn, args.force).await;
+                exit = default_logic(conn, args.force).await;
             }
         }
         Some(Subcommands::Log) => {
             tui::arg_tui("log");
         }
+        Some(Subcommands::Daemon { interval }) => {
+            daemon::run(conn, args.force, interval).await;
+        }
+
+        Some(Subcommands::Backfill {
+            item,
+            pages,
+            since,
+            select,
+        }) => {
+            let since = match since.as_deref().map(backfill::parse_since) {
+                Some(Ok(date)) => Some(date),
+                Some(Err(e)) => {
+                    println!("{}", e);
+                    return Exit::Usage;
+                }
+                None => None,
+            };
+            let backfilled =
+                backfill::run(conn, item, pages, since, select, args.check, args.force).await;
+            exit = match backfilled {
+                Ok(summary) => Exit::from_summary(&summary),
+                Err(e) => report_error("Backfill failed", e),
+            };
+        }
+
+        Some(Subcommands::History {
+            since,
+            feed,
+            item,
+            grep,
+            limit,
+        }) => {
+            let now = OffsetDateTime::now_local().unwrap_or(OffsetDateTime::now_utc());
+            let since = match since.as_deref().map(|s| history::parse_since(s, now)) {
+                Some(Ok(time)) => Some(time),
+                Some(Err(e)) => {
+                    println!("{}", e);
+                    return Exit::Usage;
+                }
+                None => None,
+            };
+            let filter = settings::HistoryFilter {
+                since,
+                feed,
+                item,
+                grep,
+                limit,
+            };
+            match settings::read_downloads(conn, &filter) {
+                Ok(downloads) => print!("{}", history::render(&downloads, format)),
+                Err(e) => exit = report_error("Failed to read download history", e),
+            }
+        }
+
+        Some(Subcommands::Config { action }) => match action {
+            ConfigAction::Export { file } => {
+                if let Err(e) = config::export(conn, file.as_deref()) {
+                    println!("Failed to export configuration: {}", e);
+                    exit = Exit::from_error(&e);
+                } else if let Some(path) = file {
+                    println!("Exported configuration to {}.", path);
+                }
+            }
+            ConfigAction::Import {
+                file,
+                dry_run,
+                prune,
+            } => {
+                if let Err(e) = config::import(conn, &file, dry_run, prune) {
+                    println!("Failed to import {}: {}", file, e);
+                    exit = Exit::from_error(&e);
+                }
+            }
+        },
+
+        Some(Subcommands::Db { action }) => match action {
+            DbAction::Migrate { dry_run: true } => {
+                let reports = match migrations::dry_run(conn) {
+                    Ok(reports) => reports,
+                    Err(e) => return report_error("Failed to plan migrations", e),
+                };
+                if reports.is_empty() {
+                    println!("Database is up to date.");
+                }
+                for report in reports {
+                    println!(
+                        "Migration {} ({}): {}",
+                        report.migration.version,
+                        report.migration.db_ver,
+                        report.migration.description
+                    );
+                    for change in report.changes {
+                        println!("    {}", change);
+                    }
+                }
+            }
+            DbAction::Migrate { dry_run: false } => match settings::get_db_ver(conn) {
+                Ok(0) => println!("Database is up to date."),
+                Ok(_) => {}
+                Err(e) => exit = report_error("Failed to migrate database", e),
+            },
+            DbAction::Backup { path } => match backup::backup(conn, path.as_deref()) {
+                Ok(dest) => println!("Backed up database to {}.", dest.display()),
+                Err(e) => {
+                    println!("Failed to back up database: {}", e);
+                    exit = Exit::from(&e);
+                }
+            },
+            DbAction::Restore { path } => match backup::restore(&path) {
+                Ok(previous) => {
+                    println!("Restored database from {}.", path);
+                    println!("The previous database was saved to {}.", previous.display());
+                }
+                Err(e) => {
+                    println!("Failed to restore {}: {}", path, e);
+                    exit = Exit::from(&e);
+                }
+            },
+        },
+    }
+    exit
+}
+
+/// Logs and prints an error that stopped a command, and picks the exit
+/// code for it.
+fn report_error(context: &str, e: impl Into<anyhow::Error>) -> Exit {
+    let e = e.into();
+    error!("{}: {}", context, e);
+    status!("{}: {}", context, e);
+    if let Some(hint) = e.downcast_ref::<Error>().and_then(Error::hint) {
+        status!("{}", hint);
     }
+    Exit::from_error(&e)
 }
 
-async fn default_logic(conn: &Connection, force: bool) {
+/// Changes a setting from the command line. A value that doesn't fit the
+/// setting is a usage error.
+fn set_setting(conn: &Connection, key: &str, value: &str) -> Exit {
+    match settings::arg_set(conn, key, value) {
+        Ok(()) => Exit::Ok,
+        Err(e) => report_error(&format!("Couldn't set {}", key), e),
+    }
+}
+
+async fn default_logic(conn: &Connection, force: bool) -> Exit {
     if force {
         debug!("Force flag set");
     } else {
         debug!("Nyaadle started normally.");
     }
-    parse::feed_parser(conn, false, force, None, None, None).await.unwrap();
+    match parse::feed_parser(conn, false, force, None, None, None).await {
+        Ok(summary) => Exit::from_summary(&summary),
+        Err(e) => report_error("Nyaadle run failed", e),
+    }
 }
 
-fn item_builder(val: Option<String>, opt: Option<String>) -> (String, String) {
+/// Every column of the given feeds, for machine-readable output.
+fn feeds_output(feeds: impl Iterator<Item = settings::Feed>) -> Output {
+    let mut out = Output::new(&[
+        ("id", "ID"),
+        ("name", "Feed Name"),
+        ("url", "URL"),
+        ("default", "Default"),
+        ("kind", "Type"),
+        ("enabled", "Enabled"),
+        ("interval_minutes", "Interval"),
+        ("last_checked", "Last Checked"),
+        ("categories", "Categories"),
+    ]);
+    for f in feeds {
+        out.row(vec![
+            Json::from(f.id),
+            Json::from(f.name),
+            Json::from(f.url),
+            Json::from(f.is_default),
+            Json::from(f.kind.as_str()),
+            Json::from(f.enabled),
+            Json::from(f.interval_minutes),
+            Json::from(f.last_checked.map(output::utc_time)),
+            Json::from(f.categories),
+        ]);
+    }
+    out
+}
+
+/// What a `--check` run would download or skip, and why.
+fn plan_output(found: &[parse::Match]) -> Output {
+    let mut out = Output::new(&[
+        ("feed", "Feed Name"),
+        ("watch_id", "ID"),
+        ("item", "Item Title"),
+        ("option", "Option"),
+        ("title", "Title"),
+        ("url", "URL"),
+        ("action", "Action"),
+        ("reason", "Reason"),
+        ("destination", "Destination"),
+    ]);
+    for m in found {
+        let action = if m.would_download() {
+            "download"
+        } else {
+            "skip"
+        };
+        out.row(vec![
+            Json::from(m.feed.clone()),
+            Json::from(m.watch_id),
+            Json::from(m.item.clone()),
+            Json::from(m.option.clone()),
+            Json::from(m.title.clone()),
+            Json::from(m.url.clone()),
+            Json::from(action),
+            Json::from(m.skip.map(|skip| skip.as_str())),
+            Json::from(m.destination.clone()),
+        ]);
+    }
+    out
+}
+
+/// The value and origin of each setting in `keys`, for machine-readable
+/// output.
+fn settings_output(conn: &Connection, keys: &[&str]) -> Output {
+    let mut out = Output::new(&[
+        ("setting", "Setting"),
+        ("value", "Value"),
+        ("origin", "Origin"),
+    ]);
+    for key in keys {
+        let (value, origin) = match *key {
+            "url" => (settings::get_url(conn), settings::Origin::Database),
+            _ => settings::resolve_setting(conn, key)
+                .unwrap_or((String::new(), settings::Origin::Default)),
+        };
+        out.row(vec![
+            Json::from(*key),
+            Json::from(value),
+            Json::from(origin.to_string()),
+        ]);
+    }
+    out
+}
+
+fn item_builder(val: Option<String>, opt: Option<String>) -> Option<(String, String)> {
     match (val, opt) {
-        (Some(v), Some(o)) => (v, o),
+        (Some(v), Some(o)) => Some((v, o)),
         _ => {
             println!("Please provide both an item name and an item option.");
-            std::process::exit(0);
+            None
         }
     }
 }
+
+/// Returns the feed `--name`, or prints that it's needed to `action` a feed.
+fn feed_name(name: Option<String>, action: &str) -> Option<String> {
+    if name.is_none() {
+        println!("Please provide the --name of the feed to {}.", action);
+    }
+    name
+}
+
+/// Finds the feed called `name`, or prints that there isn't one.
+fn find_feed<'a>(feeds: &'a [settings::Feed], name: &str) -> Option<&'a settings::Feed> {
+    let feed = feeds.iter().find(|f| f.name == name);
+    if feed.is_none() {
+        println!("Feed \"{}\" not found.", name);
+    }
+    feed
+}
+
+/// Reads a line typed in answer to a prompt.
+fn read_line() -> std::io::Result<String> {
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(input)
+}
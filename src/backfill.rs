@@ -0,0 +1,372 @@
+use crate::error::{Error, Result};
+use crate::parse::{self, Summary};
+use crate::settings;
+use crate::settings::{Feed, FeedKind, Watchlist};
+use rusqlite::Connection;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use time::format_description::well_known::Rfc2822;
+use time::{Date, OffsetDateTime};
+
+/// Placeholder in a search feed's URL that is replaced by the page number.
+/// Search URLs without it get a `p` query parameter instead.
+pub const PAGE_PLACEHOLDER: &str = "{page}";
+
+/// Number of results asked for on each page of a Torznab search.
+const TORZNAB_PAGE_SIZE: u32 = 100;
+
+/// A matching item found while walking back through a feed.
+struct Episode {
+    item: rss::Item,
+    published: Option<OffsetDateTime>,
+}
+
+/// Looks back through a watch-list item's feed for episodes released
+/// before it was added, lists them oldest first and downloads the ones
+/// picked by `select` (or by a prompt if it isn't given).
+///
+/// Pages are requested until `pages` is reached, a page comes back empty
+/// or with nothing new, or a page reaches past `since`. Downloads go
+/// through the same path as a normal run, so they land in the archive and
+/// won't be grabbed again, but the tracking table is never moved back to
+/// an older episode. An episode that fails to download is logged and
+/// counted without stopping the rest. With `check` set the episodes are
+/// only listed.
+pub async fn run(
+    conn: &Connection,
+    item_id: i32,
+    pages: u32,
+    since: Option<Date>,
+    select: Option<String>,
+    check: bool,
+    force: bool,
+) -> Result<Summary> {
+    let mut summary = Summary::default();
+    let entry = settings::read_watch_list(conn)?
+        .into_iter()
+        .find(|w| w.id == item_id)
+        .ok_or_else(|| Error::Config(format!("no watch-list item with ID {}", item_id)))?;
+    let feed = settings::read_feeds(conn)?
+        .into_iter()
+        .find(|f| f.id == entry.feed_id)
+        .ok_or_else(|| Error::Config(format!("watch-list item {} has no feed", item_id)))?;
+
+    println!(
+        "Looking back through {} for {}...\n",
+        feed.name, entry.title
+    );
+    let episodes = collect_episodes(conn, &feed, &entry, pages, since).await?;
+    if episodes.is_empty() {
+        println!("No earlier episodes found for {}.", entry.title);
+        return Ok(summary);
+    }
+
+    for (idx, ep) in episodes.iter().enumerate() {
+        let date = ep
+            .published
+            .map(|d| settings::format_unix_time(d.unix_timestamp()))
+            .unwrap_or_default();
+        println!(
+            "{:>4}. {}  {}",
+            idx + 1,
+            ep.item.title().unwrap_or("unknown"),
+            date
+        );
+    }
+    println!();
+
+    if check {
+        return Ok(summary);
+    }
+
+    let selection = match select {
+        Some(s) => s,
+        None => prompt("Episodes to download (e.g. 1-5,8 or all): ")?,
+    };
+    let picked = parse_selection(&selection, episodes.len())?;
+
+    let previous = settings::get_tracking(conn, &entry.title, entry.feed_id)?;
+    let mut newest = None;
+    for idx in picked {
+        let item = &episodes[idx].item;
+        match parse::download_logic(conn, item, &entry, &feed.name, force).await {
+            Ok(1) => {
+                summary.downloaded += 1;
+                newest = Some(idx);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                summary.failed_downloads += 1;
+                parse::download_failed(Some(&feed.name), item.title().unwrap_or("unknown"), &e);
+            }
+        }
+    }
+
+    // Each download above moved the tracking table to that episode.
+    let pointer = tracking_after(&episodes, newest, &previous);
+    if !pointer.is_empty() {
+        settings::update_tracking(conn, &entry.title, pointer, entry.feed_id)?;
+    }
+
+    info!(
+        "Backfilled {} items for {} ({} failed).",
+        summary.downloaded, entry.title, summary.failed_downloads
+    );
+    Ok(summary)
+}
+
+/// Picks what the tracking table should hold after a backfill: the newest
+/// episode downloaded (`newest`, an index into `episodes`), unless the
+/// item it held before is newer. An item that isn't in `episodes` is
+/// assumed to be newer, since a backfill looks for earlier episodes.
+fn tracking_after<'a>(
+    episodes: &'a [Episode],
+    newest: Option<usize>,
+    previous: &'a str,
+) -> &'a str {
+    let newest = match newest {
+        Some(idx) => idx,
+        None => return previous,
+    };
+    let title = episodes[newest].item.title().unwrap_or_default();
+    let previous_idx = episodes
+        .iter()
+        .position(|ep| ep.item.title() == Some(previous));
+    if previous.is_empty() || previous_idx.is_some_and(|idx| idx < newest) {
+        title
+    } else {
+        previous
+    }
+}
+
+/// Walks through the feed's pages collecting items that match `entry`,
+/// sorted oldest first.
+async fn collect_episodes(
+    conn: &Connection,
+    feed: &Feed,
+    entry: &Watchlist,
+    pages: u32,
+    since: Option<Date>,
+) -> Result<Vec<Episode>> {
+    let mut episodes: Vec<Episode> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+
+    for page in 1..=pages {
+        let url = page_url(feed, &entry.title, page)?;
+        debug!("Backfill fetching {}", url);
+        let items = parse::fetch_items(conn, &feed.name, &url).await?;
+
+        let mut new_items = 0;
+        let mut past_since = false;
+        for item in items {
+            if !seen.insert(parse::item_key(&item)) {
+                continue;
+            }
+            new_items += 1;
+
+            let published = item
+                .pub_date()
+                .and_then(|d| OffsetDateTime::parse(d, &Rfc2822).ok());
+            if let (Some(since), Some(published)) = (since, published) {
+                if published.date() < since {
+                    past_since = true;
+                    continue;
+                }
+            }
+            if item.title().is_some_and(|t| parse::is_match(t, entry)) {
+                episodes.push(Episode { item, published });
+            }
+        }
+
+        // Feeds that don't page keep returning the same items.
+        if new_items == 0 || past_since {
+            break;
+        }
+    }
+
+    // Results come newest first; reversing keeps undated items in order.
+    episodes.reverse();
+    episodes.sort_by_key(|ep| ep.published);
+    Ok(episodes)
+}
+
+/// Builds the URL for one page of results for `query`.
+///
+/// Search feeds fill in `{query}` and `{page}` (or add `p`), Torznab
+/// feeds use `offset`, and RSS feeds get nyaa-style `q` and `p`
+/// parameters.
+fn page_url(feed: &Feed, query: &str, page: u32) -> Result<String> {
+    let page = page.max(1);
+    match feed.kind {
+        FeedKind::Search => {
+            if !feed.url.contains(parse::QUERY_PLACEHOLDER) {
+                return Err(Error::Config(format!(
+                    "search feed {} has no {} placeholder in its URL",
+                    feed.name,
+                    parse::QUERY_PLACEHOLDER
+                )));
+            }
+            let url = feed
+                .url
+                .replace(parse::QUERY_PLACEHOLDER, &parse::encode_query(query));
+            if url.contains(PAGE_PLACEHOLDER) {
+                Ok(url.replace(PAGE_PLACEHOLDER, &page.to_string()))
+            } else {
+                with_page(feed, &url, page)
+            }
+        }
+        FeedKind::Torznab => {
+            let url = parse::torznab_url(feed, query)?;
+            let offset = (page - 1) * TORZNAB_PAGE_SIZE;
+            let mut url = parse_url(feed, &url)?;
+            url.query_pairs_mut()
+                .append_pair("limit", &TORZNAB_PAGE_SIZE.to_string())
+                .append_pair("offset", &offset.to_string());
+            Ok(url.into())
+        }
+        FeedKind::Rss => {
+            let mut url = parse_url(feed, &feed.url)?;
+            url.query_pairs_mut().append_pair("q", query);
+            with_page(feed, url.as_str(), page)
+        }
+    }
+}
+
+/// Adds a `p` parameter to `url` for every page after the first.
+fn with_page(feed: &Feed, url: &str, page: u32) -> Result<String> {
+    if page == 1 {
+        return Ok(url.to_string());
+    }
+    let mut url = parse_url(feed, url)?;
+    url.query_pairs_mut().append_pair("p", &page.to_string());
+    Ok(url.into())
+}
+
+/// Parses one of `feed`'s URLs.
+fn parse_url(feed: &Feed, url: &str) -> Result<reqwest::Url> {
+    reqwest::Url::parse(url)
+        .map_err(|e| Error::Config(format!("invalid URL for feed {}: {}", feed.name, e)))
+}
+
+/// Parses a `YYYY-MM-DD` date given to `--since`.
+pub fn parse_since(date: &str) -> Result<Date> {
+    let format = time::macros::format_description!("[year]-[month]-[day]");
+    Date::parse(date, &format).map_err(|e| Error::Config(format!("invalid date '{}': {}", date, e)))
+}
+
+/// Turns a selection such as `1-5,8` or `all` into zero-based indices into
+/// a list of `len` episodes, in ascending order without repeats.
+pub fn parse_selection(selection: &str, len: usize) -> Result<Vec<usize>> {
+    let selection = selection.trim();
+    if selection.eq_ignore_ascii_case("all") {
+        return Ok((0..len).collect());
+    }
+
+    let number = |n: &str| {
+        n.trim()
+            .parse::<usize>()
+            .map_err(|_| Error::Config(format!("'{}' is not an episode number", n.trim())))
+    };
+    let mut picked: Vec<usize> = Vec::new();
+    for part in selection
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+    {
+        let (start, end) = match part.split_once('-') {
+            Some((a, b)) => (number(a)?, number(b)?),
+            None => {
+                let n = number(part)?;
+                (n, n)
+            }
+        };
+        if start == 0 || end < start || end > len {
+            return Err(Error::Config(format!("'{}' is outside 1-{}", part, len)));
+        }
+        picked.extend(start - 1..end);
+    }
+    picked.sort_unstable();
+    picked.dedup();
+    Ok(picked)
+}
+
+/// Prints `message` and reads a line from stdin.
+fn prompt(message: &str) -> Result<String> {
+    print!("{}", message);
+    io::stdout().flush().map_err(Error::fs("write", "stdout"))?;
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .map_err(Error::fs("read", "stdin"))?;
+    Ok(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(kind: FeedKind, url: &str) -> Feed {
+        let mut feed = Feed::temporary(url.to_string());
+        feed.kind = kind;
+        feed
+    }
+
+    #[test]
+    fn selection_accepts_ranges_and_single_episodes() {
+        assert_eq!(parse_selection("1-3, 5,2", 6).unwrap(), vec![0, 1, 2, 4]);
+        assert_eq!(parse_selection("all", 3).unwrap(), vec![0, 1, 2]);
+        assert!(parse_selection("", 3).unwrap().is_empty());
+        assert!(parse_selection("2-7", 6).is_err());
+        assert!(parse_selection("0", 6).is_err());
+    }
+
+    #[test]
+    fn tracking_never_moves_back_to_an_older_episode() {
+        let episodes: Vec<Episode> = ["ep 1", "ep 2", "ep 3"]
+            .iter()
+            .map(|title| {
+                let mut item = rss::Item::default();
+                item.set_title(title.to_string());
+                Episode {
+                    item,
+                    published: None,
+                }
+            })
+            .collect();
+
+        assert_eq!(tracking_after(&episodes, Some(1), "ep 7"), "ep 7");
+        assert_eq!(tracking_after(&episodes, Some(2), "ep 1"), "ep 3");
+        assert_eq!(tracking_after(&episodes, Some(0), ""), "ep 1");
+        assert_eq!(tracking_after(&episodes, None, "ep 7"), "ep 7");
+    }
+
+    #[test]
+    fn bad_selections_are_usage_errors() {
+        assert!(matches!(parse_selection("1-x", 3), Err(Error::Config(_))));
+        assert!(matches!(parse_since("yesterday"), Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn pages_are_requested_per_feed_kind() {
+        let search = feed(FeedKind::Search, "https://nyaa.si/?page=rss&q={query}");
+        assert_eq!(
+            page_url(&search, "Frieren", 1).unwrap(),
+            "https://nyaa.si/?page=rss&q=Frieren"
+        );
+        assert_eq!(
+            page_url(&search, "Frieren", 3).unwrap(),
+            "https://nyaa.si/?page=rss&q=Frieren&p=3"
+        );
+
+        let paged = feed(FeedKind::Search, "https://example.com/s/{query}/{page}.rss");
+        assert_eq!(
+            page_url(&paged, "Frieren", 2).unwrap(),
+            "https://example.com/s/Frieren/2.rss"
+        );
+
+        let torznab = feed(FeedKind::Torznab, "http://127.0.0.1/api");
+        assert!(page_url(&torznab, "Frieren", 3)
+            .unwrap()
+            .ends_with("q=Frieren&limit=100&offset=200"));
+    }
+}
@@ -0,0 +1,238 @@
+use crate::error::{Error, Result};
+use crate::settings;
+use crate::status;
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+
+/// Name added to backups taken automatically before a migration. Only
+/// these are rotated; backups made with `db backup` are left alone.
+const AUTO_TAG: &str = "pre-migration";
+
+/// Copies the database to `dest` using SQLite's online backup API, so the
+/// copy is consistent even while another nyaadle process is writing.
+/// Without `dest` the backup goes to the backups folder next to the
+/// database. Returns the path written.
+pub fn backup(conn: &Connection, dest: Option<&str>) -> Result<PathBuf> {
+    let dest = match dest {
+        Some(path) => PathBuf::from(path),
+        None => backup_path(&db_path(conn)?, None)?,
+    };
+    if dest.exists() {
+        return Err(Error::fs("back up to", &dest)(
+            io::ErrorKind::AlreadyExists.into(),
+        ));
+    }
+    write_backup(conn, &dest)?;
+
+    info!("Backed up database to {}.", dest.display());
+    Ok(dest)
+}
+
+/// Replaces the database with the backup at `src`. The current database
+/// is backed up first, and the restored one is migrated if it is older.
+/// Holds the run lock throughout, so refuses while a run is in progress.
+pub fn restore(src: &str) -> Result<PathBuf> {
+    check_backup(Path::new(src))?;
+
+    let mut target = settings::open_conn()?;
+    let lock_file = settings::lock_path(&target).unwrap_or_default();
+    let _lock = match settings::try_run_lock(&target).map_err(Error::fs("lock", &lock_file))? {
+        Some(lock) => lock,
+        None => {
+            return Err(Error::Config(format!(
+                "another nyaadle run holds {}; try again once it finishes",
+                lock_file.display()
+            )))
+        }
+    };
+    let safety = backup_path(&db_path(&target)?, Some("pre-restore"))?;
+    write_backup(&target, &safety)?;
+
+    target.restore(
+        DatabaseName::Main,
+        src,
+        None::<fn(rusqlite::backup::Progress)>,
+    )?;
+    settings::get_db_ver(&target)?;
+
+    info!("Restored database from {}.", src);
+    Ok(safety)
+}
+
+/// Takes a backup before migrations run and deletes the oldest automatic
+/// backups beyond `backup-keep`. In-memory databases are skipped.
+pub fn before_migration(conn: &Connection) -> Result<()> {
+    let Some(db) = settings::db_file(conn) else {
+        return Ok(());
+    };
+    let dest = backup_path(&db, Some(AUTO_TAG))?;
+    status!(
+        "Backing up database to {} before running migrations...",
+        dest.display()
+    );
+    write_backup(conn, &dest)?;
+
+    let keep = settings::get_backup_keep(conn) as usize;
+    for old in rotate(&backups_dir(&db), &auto_prefix(&db), keep)? {
+        debug!("Removed old backup {}", old.display());
+    }
+    Ok(())
+}
+
+/// Runs the online backup into `dest`, creating its folder if needed.
+fn write_backup(conn: &Connection, dest: &Path) -> Result<()> {
+    if let Some(dir) = dest.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(Error::fs("create", dir))?;
+    }
+    conn.backup(DatabaseName::Main, dest, None)?;
+    Ok(())
+}
+
+/// Makes sure `src` is a nyaadle database before anything is overwritten.
+fn check_backup(src: &Path) -> Result<()> {
+    if !src.is_file() {
+        return Err(Error::fs("read", src)(io::ErrorKind::NotFound.into()));
+    }
+    let conn = Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let db_ver = conn
+        .query_row(
+            "SELECT path FROM directories WHERE option = 'db-ver'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .map_err(|_| Error::Config(format!("{} is not a nyaadle database", src.display())))?;
+    debug!("Restoring a version {} database", db_ver);
+    Ok(())
+}
+
+/// Returns the file behind `conn`. An in-memory database has no backups
+/// folder to default to.
+fn db_path(conn: &Connection) -> Result<PathBuf> {
+    settings::db_file(conn).ok_or_else(|| {
+        Error::Config(String::from(
+            "an in-memory database has no backups folder; give a destination",
+        ))
+    })
+}
+
+/// The folder backups go to by default: `backups/` next to the database
+/// at `db`.
+pub fn backups_dir(db: &Path) -> PathBuf {
+    db.parent().unwrap_or(Path::new("")).join("backups")
+}
+
+/// Builds a timestamped path in the backups folder of the database at
+/// `db`, such as `nyaadle-pre-migration-20240101-120000.db`.
+fn backup_path(db: &Path, tag: Option<&str>) -> Result<PathBuf> {
+    let format = time::macros::format_description!("[year][month][day]-[hour][minute][second]");
+    let now = OffsetDateTime::now_local()
+        .unwrap_or(OffsetDateTime::now_utc())
+        .format(&format)
+        .unwrap_or_default();
+    let stem = match tag {
+        Some(tag) => format!("{}-{}-{}", db_stem(db), tag, now),
+        None => format!("{}-{}", db_stem(db), now),
+    };
+    // Two backups within the same second get a counter instead of
+    // overwriting each other.
+    (1..=u32::MAX)
+        .map(|n| match n {
+            1 => backups_dir(db).join(format!("{}.db", stem)),
+            n => backups_dir(db).join(format!("{}-{}.db", stem, n)),
+        })
+        .find(|path| !path.exists())
+        .ok_or_else(|| {
+            Error::fs("name a backup in", backups_dir(db))(io::ErrorKind::AlreadyExists.into())
+        })
+}
+
+fn db_stem(db: &Path) -> String {
+    db.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("nyaadle"))
+}
+
+fn auto_prefix(db: &Path) -> String {
+    format!("{}-{}-", db_stem(db), AUTO_TAG)
+}
+
+/// Deletes all but the newest `keep` files in `dir` whose names start with
+/// `prefix`, returning the ones removed. Timestamped names sort oldest
+/// first.
+fn rotate(dir: &Path, prefix: &str, keep: usize) -> Result<Vec<PathBuf>> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(Error::fs("read", dir))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(prefix) && n.ends_with(".db"))
+        })
+        .collect();
+    backups.sort();
+
+    let excess = backups.len().saturating_sub(keep);
+    let removed: Vec<PathBuf> = backups.drain(..excess).collect();
+    for path in &removed {
+        fs::remove_file(path).map_err(Error::fs("remove", path))?;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_keeps_the_newest_automatic_backups() {
+        let dir = std::env::temp_dir().join(format!("nyaadle-rotate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "nyaadle-pre-migration-20240101-000000.db",
+            "nyaadle-pre-migration-20240301-000000.db",
+            "nyaadle-pre-migration-20240201-000000.db",
+            "nyaadle-20230101-000000.db",
+            "books-pre-migration-20230101-000000.db",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let removed = rotate(&dir, "nyaadle-pre-migration-", 2).unwrap();
+        assert_eq!(
+            removed,
+            vec![dir.join("nyaadle-pre-migration-20240101-000000.db")]
+        );
+        let mut left: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            vec![
+                "books-pre-migration-20230101-000000.db",
+                "nyaadle-20230101-000000.db",
+                "nyaadle-pre-migration-20240201-000000.db",
+                "nyaadle-pre-migration-20240301-000000.db",
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backups_are_readable_databases() {
+        let dir = std::env::temp_dir().join(format!("nyaadle-backup-{}", std::process::id()));
+        let conn = Connection::open_in_memory().unwrap();
+        settings::db_create(&conn).unwrap();
+        settings::db_write_dir(&conn, "db-ver", settings::CURRENT_DB_VERSION).unwrap();
+
+        let dest = dir.join("copy.db");
+        write_backup(&conn, &dest).unwrap();
+        check_backup(&dest).unwrap();
+        assert!(check_backup(&dir.join("missing.db")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
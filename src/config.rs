@@ -0,0 +1,546 @@
+use crate::parse;
+use crate::settings;
+use crate::settings::FeedKind;
+use anyhow::{anyhow, Result};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+
+/// Settings that describe the database itself and are never exported.
+const INTERNAL_KEYS: &[&str] = &["db-ver"];
+
+/// The settings, feeds and watch-list as one TOML document.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+    #[serde(default, rename = "feed")]
+    pub feeds: Vec<FeedConfig>,
+    #[serde(default, rename = "watch")]
+    pub watchlist: Vec<WatchConfig>,
+}
+
+/// A row of the feeds table, without the fields nyaadle fills in itself.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FeedConfig {
+    pub name: String,
+    pub url: String,
+    #[serde(default = "default_kind")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub default: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub interval: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub categories: Option<String>,
+}
+
+/// A watch-list entry, linked to its feed by name.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WatchConfig {
+    pub title: String,
+    pub option: String,
+    pub feed: String,
+}
+
+fn default_kind() -> String {
+    String::from(FeedKind::Rss.as_str())
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// A single difference between the database and an imported config.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Setting {
+        key: String,
+        old: Option<String>,
+        new: String,
+    },
+    AddFeed(FeedConfig),
+    UpdateFeed {
+        old: FeedConfig,
+        new: FeedConfig,
+    },
+    RemoveFeed(FeedConfig),
+    AddWatch(WatchConfig),
+    UpdateWatch {
+        old: WatchConfig,
+        new: WatchConfig,
+    },
+    RemoveWatch(WatchConfig),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Setting {
+                key,
+                old: None,
+                new,
+            } => write!(f, "+ setting {} = {}", key, new),
+            Change::Setting {
+                key,
+                old: Some(old),
+                new,
+            } => write!(f, "~ setting {}: {} -> {}", key, old, new),
+            Change::AddFeed(feed) => write!(f, "+ feed {} ({}) {}", feed.name, feed.kind, feed.url),
+            Change::UpdateFeed { old, new } => {
+                write!(
+                    f,
+                    "~ feed {}: {}",
+                    new.name,
+                    feed_fields(old, new).join(", ")
+                )
+            }
+            Change::RemoveFeed(feed) => write!(f, "- feed {} {}", feed.name, feed.url),
+            Change::AddWatch(w) => {
+                write!(f, "+ watch \"{}\" [{}] on {}", w.title, w.option, w.feed)
+            }
+            Change::UpdateWatch { old, new } => write!(
+                f,
+                "~ watch \"{}\" on {}: option {} -> {}",
+                new.title, new.feed, old.option, new.option
+            ),
+            Change::RemoveWatch(w) => {
+                write!(f, "- watch \"{}\" [{}] on {}", w.title, w.option, w.feed)
+            }
+        }
+    }
+}
+
+/// Describes every field that differs between two versions of a feed.
+fn feed_fields(old: &FeedConfig, new: &FeedConfig) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut compare = |name: &str, a: String, b: String| {
+        if a != b {
+            fields.push(format!("{} {} -> {}", name, a, b));
+        }
+    };
+    compare("url", old.url.clone(), new.url.clone());
+    compare("kind", old.kind.clone(), new.kind.clone());
+    compare("default", old.default.to_string(), new.default.to_string());
+    compare("enabled", old.enabled.to_string(), new.enabled.to_string());
+    compare(
+        "interval",
+        old.interval.to_string(),
+        new.interval.to_string(),
+    );
+    compare(
+        "api_key",
+        mask(old.api_key.as_deref()),
+        mask(new.api_key.as_deref()),
+    );
+    compare(
+        "categories",
+        old.categories.clone().unwrap_or_default(),
+        new.categories.clone().unwrap_or_default(),
+    );
+    fields
+}
+
+/// Keeps API keys out of terminal output and scrollback.
+fn mask(key: Option<&str>) -> String {
+    match key {
+        Some(k) if !k.is_empty() => String::from("****"),
+        _ => String::from("(none)"),
+    }
+}
+
+/// Reads the current configuration out of the database.
+pub fn from_db(conn: &Connection) -> Result<Config> {
+    let mut settings = BTreeMap::new();
+    let mut stmt = conn.prepare("SELECT option, path FROM directories")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?;
+    for row in rows {
+        let (key, value) = row?;
+        if !INTERNAL_KEYS.contains(&key.as_str()) {
+            settings.insert(key, value);
+        }
+    }
+
+    let feeds = settings::read_feeds(conn)?;
+    let watchlist = settings::read_watch_list(conn)?
+        .into_iter()
+        .map(|w| WatchConfig {
+            feed: feeds
+                .iter()
+                .find(|f| f.id == w.feed_id)
+                .map(|f| f.name.clone())
+                .unwrap_or_default(),
+            title: w.title,
+            option: w.option,
+        })
+        .collect();
+    let feeds = feeds
+        .into_iter()
+        .map(|f| FeedConfig {
+            name: f.name,
+            url: f.url,
+            kind: String::from(f.kind.as_str()),
+            default: f.is_default,
+            enabled: f.enabled,
+            interval: f.interval_minutes,
+            api_key: f.api_key,
+            categories: f.categories,
+        })
+        .collect();
+
+    Ok(Config {
+        settings,
+        feeds,
+        watchlist,
+    })
+}
+
+/// Writes the configuration as TOML to `path`, or to stdout if no path is
+/// given.
+pub fn export(conn: &Connection, path: Option<&str>) -> Result<()> {
+    let document = toml::to_string_pretty(&from_db(conn)?)?;
+    match path {
+        Some(p) => fs::write(p, document)?,
+        None => print!("{}", document),
+    }
+    Ok(())
+}
+
+/// Reads the TOML file at `path`, prints what would change and, unless
+/// `dry_run` is set, applies it. Feeds and watch-list entries missing from
+/// the file are only removed with `prune`. Returns the number of changes.
+pub fn import(conn: &Connection, path: &str, dry_run: bool, prune: bool) -> Result<usize> {
+    let desired: Config = toml::from_str(&fs::read_to_string(path)?)?;
+    let current = from_db(conn)?;
+    let changes = diff(&current, &desired, prune)?;
+
+    if changes.is_empty() {
+        println!("Configuration is already up to date.");
+        return Ok(0);
+    }
+    for change in &changes {
+        println!("{}", change);
+    }
+    if dry_run {
+        println!("\nDry run: {} changes not applied.", changes.len());
+        return Ok(changes.len());
+    }
+
+    apply(conn, &changes)?;
+    println!("\nApplied {} changes.", changes.len());
+    info!("Imported configuration from {}.", path);
+    Ok(changes.len())
+}
+
+/// Works out the changes needed to turn `current` into `desired`, after
+/// checking that `desired` is consistent on its own.
+pub fn diff(current: &Config, desired: &Config, prune: bool) -> Result<Vec<Change>> {
+    let mut desired = desired.clone();
+    validate(current, &desired, prune)?;
+
+    // A file that doesn't name a default feed leaves the current one alone.
+    if !desired.feeds.iter().any(|f| f.default) {
+        for feed in &mut desired.feeds {
+            feed.default = current
+                .feeds
+                .iter()
+                .any(|c| c.name == feed.name && c.default);
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (key, value) in &desired.settings {
+        let old = current.settings.get(key);
+        if old != Some(value) {
+            changes.push(Change::Setting {
+                key: key.clone(),
+                old: old.cloned(),
+                new: value.clone(),
+            });
+        }
+    }
+
+    for feed in &desired.feeds {
+        match current.feeds.iter().find(|c| c.name == feed.name) {
+            None => changes.push(Change::AddFeed(feed.clone())),
+            Some(old) if old != feed => changes.push(Change::UpdateFeed {
+                old: old.clone(),
+                new: feed.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    for entry in &desired.watchlist {
+        match current
+            .watchlist
+            .iter()
+            .find(|c| c.title == entry.title && c.feed == entry.feed)
+        {
+            None => changes.push(Change::AddWatch(entry.clone())),
+            Some(old) if old != entry => changes.push(Change::UpdateWatch {
+                old: old.clone(),
+                new: entry.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    if prune {
+        for entry in &current.watchlist {
+            if !desired
+                .watchlist
+                .iter()
+                .any(|d| d.title == entry.title && d.feed == entry.feed)
+            {
+                changes.push(Change::RemoveWatch(entry.clone()));
+            }
+        }
+        for feed in &current.feeds {
+            if !desired.feeds.iter().any(|d| d.name == feed.name) {
+                changes.push(Change::RemoveFeed(feed.clone()));
+            }
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Rejects configs that would leave the database in a broken state.
+fn validate(current: &Config, desired: &Config, prune: bool) -> Result<()> {
+    for (idx, feed) in desired.feeds.iter().enumerate() {
+        let kind = FeedKind::parse(&feed.kind)
+            .ok_or_else(|| anyhow!("feed {} has unknown kind '{}'", feed.name, feed.kind))?;
+        if kind == FeedKind::Search && !feed.url.contains(parse::QUERY_PLACEHOLDER) {
+            return Err(anyhow!(
+                "search feed {} has no {} placeholder in its URL",
+                feed.name,
+                parse::QUERY_PLACEHOLDER
+            ));
+        }
+        if feed.interval < 0 {
+            return Err(anyhow!("feed {} has a negative interval", feed.name));
+        }
+        if desired.feeds[..idx].iter().any(|f| f.name == feed.name) {
+            return Err(anyhow!("feed {} is listed more than once", feed.name));
+        }
+    }
+
+    let defaults = desired.feeds.iter().filter(|f| f.default).count();
+    if defaults > 1 {
+        return Err(anyhow!("only one feed can be the default"));
+    }
+    if prune && defaults == 0 {
+        let current_default = current.feeds.iter().find(|f| f.default);
+        if let Some(feed) = current_default {
+            if !desired.feeds.iter().any(|f| f.name == feed.name) {
+                return Err(anyhow!("default feed {} would be removed", feed.name));
+            }
+        }
+    }
+
+    for (idx, entry) in desired.watchlist.iter().enumerate() {
+        let in_file = desired.feeds.iter().any(|f| f.name == entry.feed);
+        let kept = !prune && current.feeds.iter().any(|f| f.name == entry.feed);
+        if !in_file && !kept {
+            return Err(anyhow!(
+                "watch-list entry \"{}\" uses unknown feed {}",
+                entry.title,
+                entry.feed
+            ));
+        }
+        if desired.watchlist[..idx]
+            .iter()
+            .any(|w| w.title == entry.title && w.feed == entry.feed)
+        {
+            return Err(anyhow!(
+                "watch-list entry \"{}\" is listed more than once for {}",
+                entry.title,
+                entry.feed
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Applies `changes` in a single transaction so a failure leaves the
+/// database as it was.
+fn apply(conn: &Connection, changes: &[Change]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+
+    for change in changes {
+        match change {
+            Change::Setting { key, new, .. } => settings::update_write_dir(conn, key, new)?,
+            Change::AddFeed(feed) => {
+                settings::db_write_feed(conn, &feed.name, &feed.url, false)?;
+                write_feed(conn, feed)?;
+            }
+            Change::UpdateFeed { new, .. } => {
+                settings::update_feed_url(conn, &new.name, &new.url)?;
+                write_feed(conn, new)?;
+            }
+            _ => {}
+        }
+    }
+
+    // Watch-list entries refer to feeds by name, so they go in once every
+    // feed exists. Removals come last so a pruned feed takes nothing else
+    // with it.
+    let feeds = settings::read_feeds(conn)?;
+    let feed_id = |name: &str| {
+        feeds
+            .iter()
+            .find(|f| f.name == name)
+            .map(|f| f.id)
+            .ok_or_else(|| anyhow!("feed {} not found", name))
+    };
+    let watch_id = |entry: &WatchConfig| -> Result<i32> {
+        let id = feed_id(&entry.feed)?;
+        settings::read_watch_list(conn)?
+            .into_iter()
+            .find(|w| w.title == entry.title && w.feed_id == id)
+            .map(|w| w.id)
+            .ok_or_else(|| anyhow!("watch-list entry \"{}\" not found", entry.title))
+    };
+
+    for change in changes {
+        match change {
+            Change::AddWatch(entry) => {
+                settings::db_write_wl(conn, &entry.title, &entry.option, feed_id(&entry.feed)?)?
+            }
+            Change::UpdateWatch { new, .. } => {
+                settings::update_wl(conn, &new.title, &new.option, &watch_id(new)?.to_string())?
+            }
+            Change::RemoveWatch(entry) => {
+                settings::db_delete_wl(conn, &watch_id(entry)?.to_string())?
+            }
+            _ => {}
+        }
+    }
+    for change in changes {
+        if let Change::RemoveFeed(feed) = change {
+            settings::db_delete_feed(conn, &feed.name, None, None)?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// Writes the feed fields that `db_write_feed` doesn't cover.
+fn write_feed(conn: &Connection, feed: &FeedConfig) -> Result<()> {
+    let kind = FeedKind::parse(&feed.kind).unwrap_or(FeedKind::Rss);
+    settings::set_feed_source(
+        conn,
+        &feed.name,
+        kind,
+        feed.api_key.as_deref(),
+        feed.categories.as_deref(),
+    )?;
+    settings::set_feed_enabled(conn, &feed.name, feed.enabled)?;
+    settings::set_feed_interval(conn, &feed.name, feed.interval)?;
+    if feed.default {
+        settings::set_default_feed(conn, &feed.name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[settings]
+dl-dir = "/srv/torrents"
+poll-interval = "15"
+
+[[feed]]
+name = "Default"
+url = "https://nyaa.si/?page=rss"
+default = true
+
+[[feed]]
+name = "Search"
+url = "https://nyaa.si/?page=rss&q={query}"
+kind = "search"
+interval = 60
+
+[[watch]]
+title = "Frieren"
+option = "1080"
+feed = "Search"
+"#;
+
+    fn current() -> Config {
+        let mut config: Config = toml::from_str(CONFIG).unwrap();
+        config.feeds.truncate(1);
+        config.feeds.push(FeedConfig {
+            name: String::from("Old"),
+            url: String::from("https://example.com/old.rss"),
+            kind: default_kind(),
+            default: false,
+            enabled: true,
+            interval: 0,
+            api_key: None,
+            categories: None,
+        });
+        config
+            .settings
+            .insert(String::from("poll-interval"), String::from("30"));
+        config.watchlist[0].feed = String::from("Default");
+        config
+    }
+
+    #[test]
+    fn exported_config_reads_back_the_same() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let round_trip: Config = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(config, round_trip);
+        assert!(config.feeds[1].enabled);
+        assert_eq!(config.feeds[0].kind, "rss");
+    }
+
+    #[test]
+    fn import_lists_changes_and_only_removes_when_pruning() {
+        let desired: Config = toml::from_str(CONFIG).unwrap();
+        let changes = diff(&current(), &desired, false).unwrap();
+        let lines: Vec<String> = changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "~ setting poll-interval: 30 -> 15",
+                "+ feed Search (search) https://nyaa.si/?page=rss&q={query}",
+                "+ watch \"Frieren\" [1080] on Search",
+            ]
+        );
+
+        let pruned = diff(&current(), &desired, true).unwrap();
+        assert!(pruned.contains(&Change::RemoveFeed(current().feeds[1].clone())));
+        assert!(pruned.contains(&Change::RemoveWatch(current().watchlist[0].clone())));
+        assert!(diff(&current(), &desired, true)
+            .unwrap()
+            .iter()
+            .all(|c| !matches!(c, Change::RemoveFeed(f) if f.name == "Default")));
+    }
+
+    #[test]
+    fn inconsistent_configs_are_rejected() {
+        let mut desired: Config = toml::from_str(CONFIG).unwrap();
+        desired.watchlist[0].feed = String::from("Missing");
+        assert!(diff(&current(), &desired, false).is_err());
+
+        let mut desired: Config = toml::from_str(CONFIG).unwrap();
+        desired.feeds[1].url = String::from("https://nyaa.si/?page=rss");
+        assert!(diff(&current(), &desired, false).is_err());
+    }
+}
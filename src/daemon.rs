@@ -0,0 +1,144 @@
+use crate::parse;
+use crate::settings;
+use rusqlite::Connection;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::sync::watch;
+
+/// Seconds to wait before retrying a feed when another run holds the lock.
+const LOCK_RETRY_SECS: i64 = 60;
+
+/// Runs nyaadle as a long-lived process, polling every enabled feed on its
+/// own schedule until SIGINT or SIGTERM is received.
+///
+/// Feeds, the watch-list and the poll interval are re-read from the
+/// database on every cycle, so changes made through the TUI or the other
+/// subcommands take effect without restarting. A feed is due once its own
+/// interval (or the global poll interval if it has none) has passed since
+/// `last_checked`, so restarting the daemon or mixing it with cron runs
+/// doesn't re-poll feeds early. A shutdown signal is only acted on between
+/// feeds, so an in-progress download is always finished.
+pub async fn run(conn: &Connection, force: bool, interval: Option<u64>) {
+    info!("Nyaadle started in daemon mode.");
+
+    let mut shutdown = shutdown_listener();
+
+    while !*shutdown.borrow() {
+        let _ = settings::prune_logs(conn);
+        let global = interval.unwrap_or_else(|| settings::get_poll_interval(conn)) as i64 * 60;
+        let feeds = settings::read_feeds(conn).unwrap_or_default();
+        let watch_list = settings::read_watch_list(conn).unwrap_or_default();
+
+        let mut next_wake = global;
+        for feed in feeds.into_iter().filter(|f| f.enabled) {
+            if *shutdown.borrow() {
+                break;
+            }
+            let period = if feed.interval_minutes > 0 {
+                feed.interval_minutes * 60
+            } else {
+                global
+            };
+            let offset = jitter(feed.id, period);
+            let due_at = feed.last_checked.map_or(0, |last| last + period + offset);
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+
+            if due_at > now {
+                next_wake = next_wake.min(due_at - now);
+                continue;
+            }
+
+            // Wait for a cron run or another daemon to finish first.
+            let _lock = match settings::try_run_lock(conn) {
+                Ok(Some(lock)) => lock,
+                Ok(None) => {
+                    debug!("Another run is in progress. Retrying {} later.", feed.name);
+                    next_wake = next_wake.min(LOCK_RETRY_SECS);
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Failed to take the run lock: {}", e);
+                    next_wake = next_wake.min(LOCK_RETRY_SECS);
+                    continue;
+                }
+            };
+
+            debug!("Polling feed {}", feed.name);
+            let downloaded = parse::parse_feeds(conn, vec![feed], &watch_list, false, force)
+                .await
+                .downloaded;
+            if downloaded > 0 {
+                info!("Daemon downloaded {} items.", downloaded);
+            }
+            next_wake = next_wake.min(period + offset);
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(next_wake.max(1) as u64)) => {}
+            _ = shutdown.changed() => {}
+        }
+    }
+
+    info!("Nyaadle daemon stopped.");
+}
+
+/// Spawns a task that flips the returned flag once SIGINT or SIGTERM
+/// arrives. Registering the handlers up front overrides the default
+/// behaviour of killing the process mid-download.
+fn shutdown_listener() -> watch::Receiver<bool> {
+    let (tx, rx) = watch::channel(false);
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        println!("Shutting down after the current feed finishes...");
+        let _ = tx.send(true);
+    });
+    rx
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut term = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM.");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = term.recv() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Returns a delay in seconds, up to a tenth of `period`, so that feeds
+/// sharing an interval don't all hit the site at the same moment. It is
+/// derived from the feed ID by Fibonacci hashing, which spreads
+/// consecutive IDs evenly, so each feed keeps the same slot across runs.
+fn jitter(feed_id: i32, period: i64) -> i64 {
+    let max = period / 10;
+    if max <= 0 {
+        return 0;
+    }
+    let hash = (feed_id as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32;
+    (hash % max as u64) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jitter_spreads_feeds_within_a_tenth_of_the_period() {
+        let offsets: Vec<i64> = (1..=5).map(|id| jitter(id, 1800)).collect();
+        assert!(offsets.iter().all(|offset| (0..180).contains(offset)));
+        assert_eq!(
+            offsets,
+            (1..=5).map(|id| jitter(id, 1800)).collect::<Vec<_>>()
+        );
+        let mut distinct = offsets.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), offsets.len());
+        assert_eq!(jitter(1, 5), 0);
+    }
+}
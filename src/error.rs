@@ -0,0 +1,89 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Everything that can go wrong while checking feeds, downloading or
+/// changing settings. Each variant says what failed in words a user can
+/// act on; `hint` adds what to try next.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A feed or file couldn't be fetched.
+    #[error("network error: {0}")]
+    Network(#[from] reqwest::Error),
+    /// A feed answered with something nyaadle couldn't read.
+    #[error("{0}")]
+    Feed(String),
+    /// The database couldn't be opened, read or written.
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    /// A setting, feed or argument has a value that can't be used.
+    #[error("{0}")]
+    Config(String),
+    /// A file or directory couldn't be created, read or written.
+    #[error("couldn't {action} {}: {source}", path.display())]
+    Filesystem {
+        action: &'static str,
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The Discord webhook couldn't be reached.
+    #[error("couldn't send the Discord notification: {0}")]
+    Notifier(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Builds a `map_err` closure for an I/O error on `path`, e.g.
+    /// `.map_err(Error::fs("create", &dir))`.
+    pub fn fs(action: &'static str, path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Error {
+        let path = path.as_ref().to_path_buf();
+        move |source| Error::Filesystem {
+            action,
+            path,
+            source,
+        }
+    }
+
+    /// A suggestion printed after the error, if there's an obvious one.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Error::Network(_) | Error::Feed(_) => {
+                Some("Check your connection and the feed URLs with 'nyaadle feeds --print'.")
+            }
+            Error::Database(_) => {
+                Some("Check the database path, or upgrade it with 'nyaadle db migrate'.")
+            }
+            Error::Filesystem { .. } => Some(
+                "Check that the directory is writable, or change it with 'nyaadle set --set-dl-dir'.",
+            ),
+            Error::Notifier(_) => {
+                Some("Check the webhook URL with 'nyaadle set --get-webhk-url'.")
+            }
+            Error::Config(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filesystem_errors_name_the_path() {
+        let source = io::Error::new(io::ErrorKind::PermissionDenied, "permission denied");
+        let e = Error::fs("create", "/srv/downloads")(source);
+        assert_eq!(
+            e.to_string(),
+            "couldn't create /srv/downloads: permission denied"
+        );
+        assert!(e.hint().is_some());
+    }
+
+    #[test]
+    fn sources_convert_into_their_variant() {
+        let e: Error = rusqlite::Error::QueryReturnedNoRows.into();
+        assert!(matches!(e, Error::Database(_)));
+        assert!(Error::Config(String::from("bad")).hint().is_none());
+    }
+}
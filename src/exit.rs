@@ -0,0 +1,123 @@
+use crate::error::Error;
+use crate::parse::Summary;
+use std::process::ExitCode;
+
+/// How a run ended, reported as the process exit code so cron wrappers
+/// and monitoring can react without reading the output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exit {
+    /// Finished with nothing new to download.
+    Ok,
+    /// Something unexpected went wrong.
+    Failure,
+    /// The arguments or input were invalid. Matches clap's own code for
+    /// bad arguments.
+    Usage,
+    /// None of the feeds could be fetched or read.
+    Network,
+    /// The database couldn't be opened, read or written.
+    Database,
+    /// Some feeds failed while others were checked.
+    Partial,
+    /// Finished and downloaded at least one item.
+    Downloaded,
+}
+
+impl Exit {
+    pub fn code(self) -> u8 {
+        match self {
+            Exit::Ok => 0,
+            Exit::Failure => 1,
+            Exit::Usage => 2,
+            Exit::Network => 3,
+            Exit::Database => 4,
+            Exit::Partial => 5,
+            Exit::Downloaded => 10,
+        }
+    }
+
+    /// Ends the process right away with this code.
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code() as i32)
+    }
+
+    /// Picks the code for a finished run over the feeds.
+    pub fn from_summary(summary: &Summary) -> Exit {
+        if summary.feeds > 0 && summary.failed == summary.feeds {
+            Exit::Network
+        } else if summary.failed > 0 || summary.failed_downloads > 0 {
+            Exit::Partial
+        } else if summary.downloaded > 0 {
+            Exit::Downloaded
+        } else {
+            Exit::Ok
+        }
+    }
+
+    /// Picks the code for an error that stopped a command.
+    pub fn from_error(error: &anyhow::Error) -> Exit {
+        if let Some(e) = error.downcast_ref::<Error>() {
+            Exit::from(e)
+        } else if error.downcast_ref::<rusqlite::Error>().is_some() {
+            Exit::Database
+        } else if error.downcast_ref::<reqwest::Error>().is_some() {
+            Exit::Network
+        } else {
+            Exit::Failure
+        }
+    }
+}
+
+impl From<&Error> for Exit {
+    fn from(error: &Error) -> Exit {
+        match error {
+            Error::Network(_) | Error::Feed(_) => Exit::Network,
+            Error::Database(_) => Exit::Database,
+            Error::Config(_) => Exit::Usage,
+            Error::Filesystem { .. } | Error::Notifier(_) => Exit::Failure,
+        }
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> ExitCode {
+        ExitCode::from(exit.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(feeds: u32, failed: u32, downloaded: u32) -> Summary {
+        Summary {
+            feeds,
+            failed,
+            downloaded,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn runs_map_to_their_codes() {
+        assert_eq!(Exit::from_summary(&summary(0, 0, 0)), Exit::Ok);
+        assert_eq!(Exit::from_summary(&summary(2, 0, 0)), Exit::Ok);
+        assert_eq!(Exit::from_summary(&summary(2, 0, 3)), Exit::Downloaded);
+        assert_eq!(Exit::from_summary(&summary(2, 1, 3)), Exit::Partial);
+        assert_eq!(Exit::from_summary(&summary(2, 2, 0)), Exit::Network);
+
+        let mut one_bad_item = summary(2, 0, 3);
+        one_bad_item.failed_downloads = 1;
+        assert_eq!(Exit::from_summary(&one_bad_item), Exit::Partial);
+    }
+
+    #[test]
+    fn database_errors_have_their_own_code() {
+        let db = anyhow::Error::from(rusqlite::Error::QueryReturnedNoRows);
+        assert_eq!(Exit::from_error(&db), Exit::Database);
+        assert_eq!(Exit::from_error(&anyhow::anyhow!("oops")), Exit::Failure);
+
+        let config = anyhow::Error::from(Error::Config(String::from("bad value")));
+        assert_eq!(Exit::from_error(&config), Exit::Usage);
+    }
+}
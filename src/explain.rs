@@ -0,0 +1,224 @@
+use crate::error::Result;
+use crate::output::{Format, Output};
+use crate::parse::{option_matches, NON_VID};
+use crate::settings::{self, Watchlist};
+use rusqlite::Connection;
+use serde_json::Value as Json;
+
+/// How one watch-list entry's rules apply to a release title.
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    pub entry: Watchlist,
+    /// Name of the feed the entry is checked against, if it still exists.
+    pub feed: Option<String>,
+    /// Every condition in the order a run checks them.
+    pub checks: Vec<Check>,
+}
+
+impl Explanation {
+    /// Returns true if a run seeing the title would download it for this
+    /// entry.
+    pub fn matched(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+}
+
+/// One condition of a watch-list entry and whether the title met it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Check {
+    /// `title`, `option`, `feed` or `tracking`.
+    pub name: &'static str,
+    pub passed: bool,
+    /// What was compared, in words.
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, passed: bool, detail: String) -> Check {
+        Check {
+            name,
+            passed,
+            detail,
+        }
+    }
+}
+
+/// Runs `title` through every watch-list entry, checking the same things a
+/// run does before downloading an item. Whether the file is already in
+/// `ar-dir` depends on its URL, so it isn't covered.
+pub fn explain(conn: &Connection, title: &str) -> Result<Vec<Explanation>> {
+    let feeds = settings::read_feeds(conn)?;
+    let mut explanations = Vec::new();
+
+    for entry in settings::read_watch_list(conn)? {
+        let feed = feeds.iter().find(|f| f.id == entry.feed_id);
+        let tracked = settings::get_tracking(conn, &entry.title, entry.feed_id)?;
+
+        let title_passed = title.contains(&entry.title);
+        // A run skips entries without an option before matching anything.
+        let option_passed = !entry.option.is_empty() && option_matches(title, &entry.option);
+
+        let title_check = if title_passed {
+            Check::new("title", true, format!("contains \"{}\"", entry.title))
+        } else {
+            Check::new(
+                "title",
+                false,
+                format!("doesn't contain \"{}\"", entry.title),
+            )
+        };
+        let option_check = if entry.option.is_empty() {
+            Check::new("option", false, String::from("no download option is set"))
+        } else if entry.option == NON_VID {
+            Check::new("option", true, format!("{} matches any title", NON_VID))
+        } else if option_passed {
+            Check::new("option", true, format!("contains \"{}\"", entry.option))
+        } else {
+            Check::new(
+                "option",
+                false,
+                format!("doesn't contain \"{}\"", entry.option),
+            )
+        };
+        let feed_check = match feed {
+            Some(feed) if feed.enabled => {
+                Check::new("feed", true, format!("{} is enabled", feed.name))
+            }
+            Some(feed) => Check::new("feed", false, format!("{} is disabled", feed.name)),
+            None => Check::new(
+                "feed",
+                false,
+                format!("feed {} doesn't exist", entry.feed_id),
+            ),
+        };
+        let tracking_check = if tracked == title {
+            Check::new(
+                "tracking",
+                false,
+                String::from("already the last download for this entry"),
+            )
+        } else if tracked.is_empty() {
+            Check::new(
+                "tracking",
+                true,
+                String::from("nothing downloaded for this entry yet"),
+            )
+        } else {
+            Check::new(
+                "tracking",
+                true,
+                format!("last download was \"{}\"", tracked),
+            )
+        };
+
+        explanations.push(Explanation {
+            feed: feed.map(|f| f.name.clone()),
+            checks: vec![title_check, option_check, feed_check, tracking_check],
+            entry,
+        });
+    }
+    Ok(explanations)
+}
+
+/// Renders explanations in `format`, one row per condition.
+pub fn render(explanations: &[Explanation], format: Format) -> String {
+    let mut out = Output::new(&[
+        ("watch_id", "ID"),
+        ("item", "Item Title"),
+        ("feed", "Feed Name"),
+        ("check", "Check"),
+        ("result", "Result"),
+        ("detail", "Detail"),
+    ]);
+    for explanation in explanations {
+        for check in &explanation.checks {
+            out.row(vec![
+                Json::from(explanation.entry.id),
+                Json::from(explanation.entry.title.clone()),
+                Json::from(explanation.feed.clone()),
+                Json::from(check.name),
+                Json::from(if check.passed { "pass" } else { "fail" }),
+                Json::from(check.detail.clone()),
+            ]);
+        }
+    }
+    out.render(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::is_match;
+
+    fn results(explanation: &Explanation) -> Vec<(&str, bool)> {
+        explanation
+            .checks
+            .iter()
+            .map(|check| (check.name, check.passed))
+            .collect()
+    }
+
+    #[test]
+    fn each_condition_is_reported() {
+        let conn = Connection::open_in_memory().unwrap();
+        settings::db_create(&conn).unwrap();
+        settings::db_write_feed(&conn, "Nyaa", "https://nyaa.si/?page=rss", true).unwrap();
+        let feed_id = settings::read_feeds(&conn).unwrap()[0].id;
+        settings::db_write_wl(&conn, "Frieren", "1080", feed_id).unwrap();
+        settings::db_write_wl(&conn, "Frieren - 07", "720", feed_id).unwrap();
+        settings::db_write_wl(&conn, "Dandadan", NON_VID, feed_id).unwrap();
+        let title = "[Sub] Frieren - 07 [1080p].mkv";
+        settings::update_tracking(&conn, "Frieren", title, feed_id).unwrap();
+
+        let explanations = explain(&conn, title).unwrap();
+        assert_eq!(explanations.len(), 3);
+        assert_eq!(
+            results(&explanations[0]),
+            vec![
+                ("title", true),
+                ("option", true),
+                ("feed", true),
+                ("tracking", false)
+            ]
+        );
+        assert_eq!(
+            results(&explanations[1]),
+            vec![
+                ("title", true),
+                ("option", false),
+                ("feed", true),
+                ("tracking", true)
+            ]
+        );
+        assert_eq!(
+            results(&explanations[2]),
+            vec![
+                ("title", false),
+                ("option", true),
+                ("feed", true),
+                ("tracking", true)
+            ]
+        );
+        assert!(explanations.iter().all(|e| !e.matched()));
+        for explanation in &explanations {
+            let title_and_option = explanation.checks[0].passed && explanation.checks[1].passed;
+            assert_eq!(title_and_option, is_match(title, &explanation.entry));
+        }
+    }
+
+    #[test]
+    fn a_match_agrees_with_the_run() {
+        let conn = Connection::open_in_memory().unwrap();
+        settings::db_create(&conn).unwrap();
+        settings::db_write_feed(&conn, "Nyaa", "https://nyaa.si/?page=rss", true).unwrap();
+        let feed_id = settings::read_feeds(&conn).unwrap()[0].id;
+        settings::db_write_wl(&conn, "Frieren", "1080", feed_id).unwrap();
+
+        let title = "[Sub] Frieren - 08 [1080p].mkv";
+        let explanation = &explain(&conn, title).unwrap()[0];
+        assert!(explanation.matched());
+        assert!(is_match(title, &explanation.entry));
+        let csv = render(std::slice::from_ref(explanation), Format::Csv);
+        assert!(csv.starts_with("watch_id,item,"));
+    }
+}
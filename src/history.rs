@@ -0,0 +1,176 @@
+use crate::backfill;
+use crate::output::{self, Format, Output};
+use crate::settings::{self, Download};
+use anyhow::{anyhow, Result};
+use serde_json::Value as Json;
+use time::OffsetDateTime;
+
+/// Turns a `--since` value into a unix time. Accepts a `YYYY-MM-DD` date,
+/// taken as midnight in `now`'s offset, or a time before `now` such as
+/// `12h`, `7d` or `2w`.
+pub fn parse_since(value: &str, now: OffsetDateTime) -> Result<i64> {
+    let value = value.trim();
+    let unit = match value.chars().last() {
+        Some('h') => Some(3600),
+        Some('d') => Some(86400),
+        Some('w') => Some(7 * 86400),
+        _ => None,
+    };
+    if let Some(unit) = unit {
+        let amount = value[..value.len() - 1]
+            .parse::<i64>()
+            .map_err(|_| anyhow!("invalid time '{}': use a date or e.g. 12h, 7d, 2w", value))?;
+        return Ok(now.unix_timestamp() - amount * unit);
+    }
+    let date = backfill::parse_since(value)?;
+    Ok(date.midnight().assume_offset(now.offset()).unix_timestamp())
+}
+
+/// Renders downloads in `format`. The table shows local times and
+/// leaves out the IDs and URL; the other formats have every column.
+pub fn render(downloads: &[Download], format: Format) -> String {
+    if format == Format::Table {
+        if downloads.is_empty() {
+            return String::from("No downloads found.\n");
+        }
+        let mut out = Output::new(&[
+            ("downloaded_at", "Date"),
+            ("feed", "Feed"),
+            ("item", "Item"),
+            ("title", "Title"),
+        ]);
+        for d in downloads {
+            out.row(vec![
+                Json::from(settings::format_unix_time(d.downloaded_at)),
+                Json::from(d.feed.clone()),
+                Json::from(d.item.clone()),
+                Json::from(d.title.clone()),
+            ]);
+        }
+        return out.render(format);
+    }
+
+    let mut out = Output::new(&[
+        ("id", "ID"),
+        ("downloaded_at", "Date"),
+        ("feed", "Feed"),
+        ("watch_id", "Watch ID"),
+        ("item", "Item"),
+        ("title", "Title"),
+        ("url", "URL"),
+    ]);
+    for d in downloads {
+        out.row(vec![
+            Json::from(d.id),
+            Json::from(output::utc_time(d.downloaded_at)),
+            Json::from(d.feed.clone()),
+            Json::from(d.watch_id),
+            Json::from(d.item.clone()),
+            Json::from(d.title.clone()),
+            Json::from(d.url.clone()),
+        ]);
+    }
+    out.render(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+    use time::macros::datetime;
+
+    fn download(title: &str) -> Download {
+        Download {
+            id: 1,
+            downloaded_at: 1_700_000_000,
+            feed: Some(String::from("Default")),
+            watch_id: Some(3),
+            item: Some(String::from("Frieren")),
+            title: title.to_string(),
+            url: Some(String::from("https://example.com/1.torrent")),
+        }
+    }
+
+    #[test]
+    fn since_accepts_dates_and_durations() {
+        let now = datetime!(2024-03-10 12:00 UTC);
+        assert_eq!(
+            parse_since("2024-03-01", now).unwrap(),
+            datetime!(2024-03-01 0:00 UTC).unix_timestamp()
+        );
+        assert_eq!(
+            parse_since("12h", now).unwrap(),
+            datetime!(2024-03-10 0:00 UTC).unix_timestamp()
+        );
+        assert_eq!(
+            parse_since("1w", now).unwrap(),
+            datetime!(2024-03-03 12:00 UTC).unix_timestamp()
+        );
+        assert!(parse_since("soon", now).is_err());
+        assert!(parse_since("xd", now).is_err());
+    }
+
+    #[test]
+    fn csv_quotes_awkward_titles() {
+        let csv = render(&[download("[Sub] Frieren, \"S01E07\"")], Format::Csv);
+        assert_eq!(
+            csv,
+            "id,downloaded_at,feed,watch_id,item,title,url\n\
+             1,2023-11-14T22:13:20Z,Default,3,Frieren,\"[Sub] Frieren, \"\"S01E07\"\"\",https://example.com/1.torrent\n"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&[download("a")], Format::Json)).unwrap();
+        assert_eq!(json[0]["watch_id"], 3);
+        assert_eq!(json[0]["downloaded_at"], "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn filters_narrow_the_history() {
+        let conn = Connection::open_in_memory().unwrap();
+        settings::db_create(&conn).unwrap();
+        let frieren =
+            settings::Watchlist::new().build(3, String::from("Frieren"), String::new(), 1);
+        settings::write_download(&conn, Some("Default"), Some(&frieren), "Frieren - 07", "a")
+            .unwrap();
+        settings::write_download(&conn, Some("Books"), None, "Spy x Family v01", "b").unwrap();
+        settings::write_download(&conn, None, None, "https://example.com/x", "c").unwrap();
+
+        let titles = |filter: settings::HistoryFilter| -> Vec<String> {
+            settings::read_downloads(&conn, &filter)
+                .unwrap()
+                .into_iter()
+                .map(|d| d.title)
+                .collect()
+        };
+        assert_eq!(titles(Default::default()).len(), 3);
+        assert_eq!(
+            titles(settings::HistoryFilter {
+                feed: Some(String::from("default")),
+                ..Default::default()
+            }),
+            vec!["Frieren - 07"]
+        );
+        assert_eq!(
+            titles(settings::HistoryFilter {
+                item: Some(String::from("3")),
+                ..Default::default()
+            }),
+            vec!["Frieren - 07"]
+        );
+        assert_eq!(
+            titles(settings::HistoryFilter {
+                grep: Some(String::from("SPY")),
+                ..Default::default()
+            }),
+            vec!["Spy x Family v01"]
+        );
+        assert_eq!(
+            titles(settings::HistoryFilter {
+                limit: Some(1),
+                ..Default::default()
+            }),
+            vec!["https://example.com/x"]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Nyaadle checks RSS, Atom and Torznab feeds against a watch-list and
+//! downloads the items that match.
+//!
+//! [`Store`] is the way in for other programs: it opens a nyaadle
+//! database, manages its feeds and watch-list, and runs checks and
+//! downloads without going through the command line.
+//!
+//! ```no_run
+//! # async fn demo() -> nyaadle::Result<()> {
+//! let store = nyaadle::Store::open("/srv/nyaadle/nyaadle.db")?;
+//! store.add_feed("Nyaa", "https://nyaa.si/?page=rss", nyaadle::FeedKind::Rss)?;
+//! store.watch("Frieren", "1080", Some("Nyaa"))?;
+//!
+//! for found in store.check().await?.found {
+//!     if found.would_download() {
+//!         println!("{} matched {}", found.title, found.item);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The modules below are what the `nyaadle` command is built from. Only
+//! the items re-exported at the top of the crate are meant to stay stable.
+
+#[macro_use]
+extern crate log;
+extern crate time;
+
+#[cfg(feature = "discord")]
+extern crate serenity;
+
+/// This module handles the arguments passed on through the CLI.
+#[doc(hidden)]
+pub mod args;
+/// This module looks back through a feed for earlier episodes.
+pub mod backfill;
+/// This module backs up and restores the database.
+pub mod backup;
+/// This module exports and imports the whole configuration as TOML.
+pub mod config;
+/// This module runs nyaadle as a long-lived scheduler.
+pub mod daemon;
+/// This module defines the errors shared by parsing, settings and the CLI.
+pub mod error;
+/// This module maps how a run ended to the process exit code.
+pub mod exit;
+/// This module explains why a release title would or wouldn't be downloaded.
+pub mod explain;
+/// This module lists and formats the download history.
+pub mod history;
+/// This module sets up the log file.
+pub mod logging;
+/// This module applies and reports database schema migrations.
+pub mod migrations;
+/// This module imports and exports feeds as OPML.
+pub mod opml;
+/// This module prints lists and check results as text, JSON, TSV or CSV.
+pub mod output;
+/// This module handles the parsing functions of nyaadle.
+pub mod parse;
+/// This module handles all the settings and watch-list functions
+pub mod settings;
+/// This module is the entry point for using nyaadle as a library.
+pub mod store;
+/// This module creates and handles the TUI
+#[doc(hidden)]
+pub mod tui;
+
+pub use error::{Error, Result};
+pub use explain::{Check, Explanation};
+pub use parse::{is_match, Match, Skip, Summary, NON_VID};
+pub use settings::{Download, Feed, FeedKind, HistoryFilter, Watchlist};
+pub use store::Store;
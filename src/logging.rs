@@ -0,0 +1,423 @@
+use crate::error::{self, Error};
+use crate::settings::{self, LogFormat};
+use log::kv::{Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record};
+use rusqlite::Connection;
+use serde_json::{Map, Value as Json};
+use simplelog::{CombinedLogger, Config, ConfigBuilder, SharedLogger, WriteLogger};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::OffsetDateTime;
+
+/// Number of rotated log files kept, as `nyaadle.log.1` (newest) to
+/// `nyaadle.log.3`.
+pub const ROTATED_LOGS: u32 = 3;
+
+/// Only records from modules under this target go to the logs table, so
+/// the viewer isn't flooded by the HTTP client at debug levels.
+const DB_TARGET: &str = "nyaadle";
+
+/// How long the database logger waits for a lock before dropping a record.
+/// Kept short so logging never stalls a run.
+const DB_LOG_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Records from this target are left out of the log file.
+const IGNORED_TARGET: &str = "serenity";
+
+/// Sets up logging at the `log-level` setting, raised one step for every
+/// `-v` and lowered one step for every `-q`. Every record goes to the log
+/// file, as text or JSON lines depending on `log-format`, and nyaadle's own
+/// records also go to the logs table shown by the log viewer, so callers
+/// only need the `log` macros.
+///
+/// If the log file can't be opened the logs table is still set up and the
+/// error is returned, so the caller can carry on without the file.
+pub fn init(conn: &Connection, verbose: u8, quiet: u8) -> error::Result<()> {
+    let level = adjust_level(settings::get_log_level(conn), verbose, quiet);
+
+    let mut loggers: Vec<Box<dyn SharedLogger>> = Vec::new();
+    let file_error = match file_logger(conn, level) {
+        Ok(logger) => {
+            loggers.push(logger);
+            None
+        }
+        Err(e) => Some(e),
+    };
+    match DbLogger::open(conn, level) {
+        Ok(db) => loggers.push(Box::new(db)),
+        Err(e) => eprintln!("Warning: Failed to log to the database: {}", e),
+    }
+    CombinedLogger::init(loggers)
+        .map_err(|e| Error::Config(format!("couldn't set up logging: {}", e)))?;
+    file_error.map_or(Ok(()), Err)
+}
+
+/// Opens the log file at the `log` setting and returns the logger that
+/// writes to it in the `log-format` setting.
+fn file_logger(conn: &Connection, level: LevelFilter) -> error::Result<Box<dyn SharedLogger>> {
+    let log_path = settings::get_log(conn);
+    if let Some(log_dir) = Path::new(&log_path).parent() {
+        if !log_dir.as_os_str().is_empty() && !log_dir.exists() {
+            fs::create_dir_all(log_dir).map_err(Error::fs("create", log_dir))?;
+        }
+    }
+
+    let max_bytes = settings::get_log_max_size(conn) * 1024 * 1024;
+    let log_file =
+        RotatingFile::open(&log_path, max_bytes).map_err(Error::fs("open", &log_path))?;
+
+    Ok(match settings::get_log_format(conn) {
+        LogFormat::Text => {
+            let time_format = format_description!(
+                "[year]-[month repr:short]-[day] [weekday repr:short] [hour]:[minute]:[second]"
+            );
+            let mut builder = ConfigBuilder::new();
+            builder.set_time_format_custom(time_format);
+            // Times stay in UTC if the local offset can't be worked out.
+            let _ = builder.set_time_offset_to_local();
+            let conf = builder.add_filter_ignore_str(IGNORED_TARGET).build();
+            WriteLogger::new(level, conf, log_file)
+        }
+        LogFormat::Json => Box::new(JsonLogger::new(level, log_file)),
+    })
+}
+
+/// Writes records to the logs table through its own connection, with the
+/// module that logged them and any structured fields as JSON.
+struct DbLogger {
+    level: LevelFilter,
+    config: Config,
+    conn: Mutex<Connection>,
+}
+
+impl DbLogger {
+    /// Opens its own connection to the database behind `conn`.
+    fn open(conn: &Connection, level: LevelFilter) -> rusqlite::Result<DbLogger> {
+        let path = settings::db_file(conn)
+            .ok_or_else(|| rusqlite::Error::InvalidPath(PathBuf::from(":memory:")))?;
+        let conn = settings::open_conn_at(path)?;
+        conn.busy_timeout(DB_LOG_TIMEOUT)?;
+        Ok(DbLogger {
+            level,
+            config: Config::default(),
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+impl Log for DbLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && metadata.target().starts_with(DB_TARGET)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let fields = record_fields(record);
+        if let Ok(conn) = self.conn.lock() {
+            let _ = settings::write_log(
+                &conn,
+                record.level(),
+                record.target(),
+                &record.args().to_string(),
+                fields.as_deref(),
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+impl SharedLogger for DbLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&Config> {
+        Some(&self.config)
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+/// Writes each record to the log file as a single JSON object, e.g.
+/// `{"timestamp":"...","level":"info","target":"nyaadle::parse",
+/// "message":"Downloaded ...","event":"download","feed":"Default",...}`.
+struct JsonLogger {
+    level: LevelFilter,
+    config: Config,
+    file: Mutex<RotatingFile>,
+}
+
+impl JsonLogger {
+    fn new(level: LevelFilter, file: RotatingFile) -> JsonLogger {
+        JsonLogger {
+            level,
+            config: Config::default(),
+            file: Mutex::new(file),
+        }
+    }
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && !metadata.target().starts_with(IGNORED_TARGET)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let timestamp = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_default();
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(file, "{}", json_line(record, &timestamp));
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+impl SharedLogger for JsonLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&Config> {
+        Some(&self.config)
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+/// Builds the JSON log line for `record`. Its key-values are added after
+/// the standard fields, which they can't overwrite.
+fn json_line(record: &Record, timestamp: &str) -> String {
+    let mut line = Map::new();
+    line.insert(String::from("timestamp"), Json::from(timestamp));
+    line.insert(
+        String::from("level"),
+        Json::from(record.level().as_str().to_lowercase()),
+    );
+    line.insert(String::from("target"), Json::from(record.target()));
+    line.insert(
+        String::from("message"),
+        Json::from(record.args().to_string()),
+    );
+    for (key, value) in fields_of(record) {
+        line.entry(key).or_insert(value);
+    }
+    Json::Object(line).to_string()
+}
+
+/// Collects a record's key-values, e.g. `info!(feed = name; "...")`, into
+/// a JSON object. Returns `None` if it has none.
+fn record_fields(record: &Record) -> Option<String> {
+    let fields = fields_of(record);
+    if fields.is_empty() {
+        return None;
+    }
+    Some(Json::Object(fields).to_string())
+}
+
+/// Maps a record's key-values to JSON, keeping integers and booleans as
+/// such and turning anything else into a string.
+fn fields_of(record: &Record) -> Map<String, Json> {
+    struct Collect(Map<String, Json>);
+
+    impl<'kvs> VisitSource<'kvs> for Collect {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+            let value = match (value.to_i64(), value.to_bool()) {
+                (Some(n), _) => Json::from(n),
+                (_, Some(b)) => Json::from(b),
+                _ => Json::from(value.to_string()),
+            };
+            self.0.insert(key.to_string(), value);
+            Ok(())
+        }
+    }
+
+    let mut fields = Collect(Map::new());
+    let _ = record.key_values().visit(&mut fields);
+    fields.0
+}
+
+/// Moves `level` up `verbose` steps and down `quiet` steps, stopping at
+/// trace and off.
+pub fn adjust_level(level: LevelFilter, verbose: u8, quiet: u8) -> LevelFilter {
+    let levels = [
+        LevelFilter::Off,
+        LevelFilter::Error,
+        LevelFilter::Warn,
+        LevelFilter::Info,
+        LevelFilter::Debug,
+        LevelFilter::Trace,
+    ];
+    let idx = levels.iter().position(|l| *l == level).unwrap_or(3) as i32;
+    let idx = (idx + verbose as i32 - quiet as i32).clamp(0, levels.len() as i32 - 1);
+    levels[idx as usize]
+}
+
+/// A log file that is renamed to `<path>.1` once it grows past
+/// `max_bytes`, shifting older files up to `ROTATED_LOGS`. A limit of 0
+/// never rotates.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    pub fn open(path: &str, max_bytes: u64) -> io::Result<RotatingFile> {
+        let path = PathBuf::from(path);
+        let file = OpenOptions::new().append(true).create(true).open(&path)?;
+        let size = file.metadata()?.len();
+        let mut log = RotatingFile {
+            path,
+            max_bytes,
+            file,
+            size,
+        };
+        if log.max_bytes > 0 && log.size >= log.max_bytes {
+            log.rotate()?;
+        }
+        Ok(log)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        for n in (1..ROTATED_LOGS).rev() {
+            let from = rotated_path(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        self.file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_bytes > 0 && self.size > 0 && self.size + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Returns `<path>.<n>`, e.g. `nyaadle.log.2`.
+fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbosity_flags_move_the_level() {
+        assert_eq!(adjust_level(LevelFilter::Info, 1, 0), LevelFilter::Debug);
+        assert_eq!(adjust_level(LevelFilter::Info, 5, 0), LevelFilter::Trace);
+        assert_eq!(adjust_level(LevelFilter::Info, 0, 2), LevelFilter::Error);
+        assert_eq!(adjust_level(LevelFilter::Warn, 0, 9), LevelFilter::Off);
+    }
+
+    #[test]
+    fn structured_fields_are_stored_as_json() {
+        let fields: &[(&str, log::kv::Value)] = &[
+            ("feed", log::kv::Value::from("Books")),
+            ("downloaded", log::kv::Value::from(3)),
+        ];
+        let record = Record::builder()
+            .args(format_args!("Downloaded"))
+            .key_values(&fields)
+            .build();
+        assert_eq!(
+            record_fields(&record).as_deref(),
+            Some(r#"{"feed":"Books","downloaded":3}"#)
+        );
+
+        let plain = Record::builder().args(format_args!("Downloaded")).build();
+        assert_eq!(record_fields(&plain), None);
+    }
+
+    #[test]
+    fn json_lines_include_the_structured_fields() {
+        let fields: &[(&str, log::kv::Value)] = &[
+            ("event", log::kv::Value::from("download")),
+            ("watch_id", log::kv::Value::from(7)),
+            ("message", log::kv::Value::from("ignored")),
+        ];
+        let record = Record::builder()
+            .level(log::Level::Info)
+            .target("nyaadle::parse")
+            .args(format_args!("Downloaded Book"))
+            .key_values(&fields)
+            .build();
+
+        let line = json_line(&record, "2024-01-01T00:00:00Z");
+        let parsed: Json = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["timestamp"], "2024-01-01T00:00:00Z");
+        assert_eq!(parsed["level"], "info");
+        assert_eq!(parsed["target"], "nyaadle::parse");
+        assert_eq!(parsed["message"], "Downloaded Book");
+        assert_eq!(parsed["event"], "download");
+        assert_eq!(parsed["watch_id"], 7);
+        assert!(!line.contains('\n'));
+    }
+
+    #[test]
+    fn log_file_rotates_past_its_size_limit() {
+        let dir = std::env::temp_dir().join(format!("nyaadle-logs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("nyaadle.log");
+
+        let mut log = RotatingFile::open(path.to_str().unwrap(), 10).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n", "fifth\n"] {
+            log.write_all(line.as_bytes()).unwrap();
+        }
+        log.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fifth\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("nyaadle.log.1")).unwrap(),
+            "fourth\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("nyaadle.log.3")).unwrap(),
+            "second\n"
+        );
+        assert!(!dir.join("nyaadle.log.4").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
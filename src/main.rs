@@ -1,56 +1,63 @@
-/// This module handles the arguments passed on through the CLI.
-pub mod args;
-/// This module handles the parsing functions of nyaadle.
-pub mod parse;
-/// This module handles all the settings and watch-list functions
-pub mod settings;
-/// This module creates and handles the TUI
-pub mod tui;
-use simplelog::*;
-use std::fs::OpenOptions;
+use clap::Parser;
+use nyaadle::exit::Exit;
+use nyaadle::{args, logging, output, settings};
+use std::process::ExitCode;
 #[macro_use]
 extern crate log;
-extern crate time;
-
-#[cfg(feature = "discord")]
-extern crate serenity;
 
 // The main function of the program.
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
+    // The database location has to be known before anything opens it
+    let cli = args::Cli::parse();
+    output::show_status(true);
+    // Progress from opening or migrating the database must not end up in
+    // machine-readable output.
+    output::set_format(cli.output_format());
+    if let Err(e) = settings::select_db(cli.db.clone(), cli.profile.as_deref()) {
+        println!("{}", e);
+        return Exit::Usage.into();
+    }
+
     // Ensure the database exists before anything else
-    settings::set_check();
+    if let Err(e) = settings::set_check(&settings::settings_dir()) {
+        println!("{}", e);
+        if let Some(hint) = e.hint() {
+            println!("{}", hint);
+        }
+        return Exit::from(&e).into();
+    }
 
     // Open a single shared connection for the lifetime of the program
-    let conn = settings::open_conn().expect("Failed to open database.");
-
-    // Set up logging
-    let log_path = settings::get_log(&conn);
-    if let Some(log_dir) = std::path::Path::new(&log_path).parent() {
-        if !log_dir.exists() {
-            std::fs::create_dir_all(log_dir).expect("Failed to create log directory.");
+    let conn = match settings::open_conn() {
+        Ok(conn) => conn,
+        Err(e) => {
+            println!("Failed to open database: {}", e);
+            return Exit::Database.into();
         }
+    };
+    if let Err(e) = settings::load_layers(&conn, cli.config.clone(), &cli.settings) {
+        println!("{}", e);
+        return Exit::Usage.into();
     }
 
-    let time_format = format_description!(
-        "[year]-[month repr:short]-[day] [weekday repr:short] [hour]:[minute]:[second]"
-    );
-    let log_file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .create(true)
-        .open(log_path)
-        .unwrap();
-    let conf = ConfigBuilder::new()
-        .set_time_format_custom(time_format)
-        .set_time_offset_to_local()
-        .unwrap()
-        .add_filter_ignore_str("serenity")
-        .build();
-
-    WriteLogger::init(LevelFilter::Info, conf, log_file).unwrap();
+    // Set up logging
+    // Without a log file nyaadle still works, and `nyaadle set` has to
+    // keep working so the path can be fixed.
+    if let Err(e) = logging::init(&conn, cli.verbose, cli.quiet) {
+        eprintln!("Warning: Not writing a log file: {}", e);
+        eprintln!("Change the log file path with 'nyaadle set --set-log-file'.");
+    }
 
-    settings::get_db_ver(&conn).expect("Failed to set database version.");
+    // `db migrate` decides for itself whether to apply anything.
+    if !cli.runs_own_migrations() {
+        if let Err(e) = settings::get_db_ver(&conn) {
+            error!("Failed to migrate database: {}", e);
+            println!("Failed to migrate database: {}", e);
+            return Exit::Database.into();
+        }
+        let _ = settings::prune_logs(&conn);
+    }
 
-    args::args_parser(&conn).await;
+    args::args_parser(&conn, cli).await.into()
 }
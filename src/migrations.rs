@@ -0,0 +1,688 @@
+use crate::backup;
+use crate::settings;
+use crate::status;
+use rusqlite::{params, Connection};
+use std::collections::{BTreeMap, BTreeSet};
+use time::OffsetDateTime;
+
+/// A numbered schema change.
+pub struct Migration {
+    pub version: u32,
+    /// The `db-ver` value once this step has run. Kept up to date for
+    /// `--get-db-ver` and for older builds that only know about `db-ver`.
+    pub db_ver: &'static str,
+    pub description: &'static str,
+    up: fn(&Connection) -> rusqlite::Result<()>,
+}
+
+/// Every schema change since 2.x, oldest first. Steps are applied in
+/// order and recorded in `schema_migrations`; add new ones to the end and
+/// never change one that has been released.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        db_ver: "3.0",
+        description: "Move the feed URL into a feeds table and link items to it",
+        up: link_feeds,
+    },
+    Migration {
+        version: 2,
+        db_ver: "3.1",
+        description: "Add per-feed enable flag, interval and last check time",
+        up: add_feed_schedule,
+    },
+    Migration {
+        version: 3,
+        db_ver: "3.2",
+        description: "Add ETag and Last-Modified columns to feeds",
+        up: add_feed_cache,
+    },
+    Migration {
+        version: 4,
+        db_ver: "3.3",
+        description: "Allow settings to share a value",
+        up: relax_directories_unique,
+    },
+    Migration {
+        version: 5,
+        db_ver: "3.4",
+        description: "Add feed type, API key and categories",
+        up: add_feed_source,
+    },
+    Migration {
+        version: 6,
+        db_ver: "3.5",
+        description: "Record when each log entry was written",
+        up: add_log_times,
+    },
+    Migration {
+        version: 7,
+        db_ver: "3.6",
+        description: "Record which module wrote each log entry and its fields",
+        up: add_log_source,
+    },
+    Migration {
+        version: 8,
+        db_ver: "3.7",
+        description: "Keep a history of downloads",
+        up: add_downloads,
+    },
+];
+
+/// What a single migration changed, as reported by `dry_run`.
+pub struct Report {
+    pub migration: &'static Migration,
+    pub changes: Vec<String>,
+}
+
+/// Applies every pending migration, each in its own transaction, and
+/// returns the ones that ran. A backup is taken first if anything is
+/// pending.
+pub fn run(conn: &Connection) -> rusqlite::Result<Vec<&'static Migration>> {
+    prepare(conn)?;
+    let pending = pending(conn)?;
+    if pending.is_empty() {
+        return Ok(pending);
+    }
+
+    if let Err(e) = backup::before_migration(conn) {
+        status!(
+            "Warning: Pre-migration backup failed: {}. Attempting migration anyway.",
+            e
+        );
+    }
+
+    for migration in &pending {
+        let tx = conn.unchecked_transaction()?;
+        (migration.up)(conn)?;
+        record(conn, migration)?;
+        tx.commit()?;
+        info!(
+            "Applied migration {}: {}",
+            migration.version, migration.description
+        );
+        status!(
+            "Migrated database to {} ({})",
+            migration.db_ver,
+            migration.description
+        );
+    }
+    Ok(pending)
+}
+
+/// Runs every pending migration inside a transaction that is rolled back,
+/// reporting the tables, columns and row counts each one would change.
+pub fn dry_run(conn: &Connection) -> rusqlite::Result<Vec<Report>> {
+    let tx = conn.unchecked_transaction()?;
+    prepare(conn)?;
+
+    let mut reports = Vec::new();
+    for migration in pending(conn)? {
+        let before = snapshot(conn)?;
+        (migration.up)(conn)?;
+        record(conn, migration)?;
+        let changes = schema_changes(&before, &snapshot(conn)?);
+        reports.push(Report { migration, changes });
+    }
+
+    tx.rollback()?;
+    Ok(reports)
+}
+
+/// Returns the migrations that haven't been applied yet.
+fn pending(conn: &Connection) -> rusqlite::Result<Vec<&'static Migration>> {
+    let mut stmt = conn.prepare("SELECT version FROM schema_migrations")?;
+    let applied = stmt
+        .query_map([], |row| row.get::<_, u32>(0))?
+        .collect::<rusqlite::Result<BTreeSet<u32>>>()?;
+    Ok(MIGRATIONS
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+        .collect())
+}
+
+/// Creates `schema_migrations` and, for databases from before it existed,
+/// marks the steps their `db-ver` already covers as applied.
+fn prepare(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version     INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at  TEXT NOT NULL)",
+        [],
+    )?;
+    let recorded: i64 = conn.query_row("SELECT COUNT(*) FROM schema_migrations", [], |row| {
+        row.get(0)
+    })?;
+    if recorded > 0 {
+        return Ok(());
+    }
+
+    let legacy = legacy_version(conn)?;
+    for migration in MIGRATIONS.iter().filter(|m| m.version <= legacy) {
+        conn.execute(
+            "INSERT INTO schema_migrations (version, description, applied_at)
+             VALUES (?1, ?2, 'before schema_migrations')",
+            params![migration.version, migration.description],
+        )?;
+    }
+    Ok(())
+}
+
+/// Maps the `db-ver` of a database that predates `schema_migrations` to
+/// the last migration it already has.
+fn legacy_version(conn: &Connection) -> rusqlite::Result<u32> {
+    let db_ver = settings::get_stored_setting(conn, "db-ver")?;
+    let version = match MIGRATIONS.iter().find(|m| m.db_ver == db_ver) {
+        Some(m) => m.version,
+        None if db_ver.is_empty() || db_ver.starts_with("1.") || db_ver.starts_with("2.") => 0,
+        None => {
+            warn!(
+                "Unknown database version {}. Assuming it is up to date.",
+                db_ver
+            );
+            MIGRATIONS.last().map_or(0, |m| m.version)
+        }
+    };
+    // Pre-release 3.0 databases say db-ver = 3.0 but predate multi-feed,
+    // so they still need the first step.
+    if version == 1 && !watchlist_has_feed_id(conn)? {
+        return Ok(0);
+    }
+    Ok(version)
+}
+
+/// Marks `migration` as applied and updates `db-ver` to match.
+fn record(conn: &Connection, migration: &Migration) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO schema_migrations (version, description, applied_at) VALUES (?1, ?2, ?3)",
+        params![
+            migration.version,
+            migration.description,
+            OffsetDateTime::now_utc().unix_timestamp().to_string()
+        ],
+    )?;
+    settings::update_write_dir(conn, "db-ver", migration.db_ver)
+}
+
+/// Columns and row count of every table, keyed by table name.
+type Snapshot = BTreeMap<String, (String, Vec<String>, i64)>;
+
+fn snapshot(conn: &Connection) -> rusqlite::Result<Snapshot> {
+    let mut stmt = conn.prepare(
+        "SELECT name, sql FROM sqlite_master
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name != 'schema_migrations'",
+    )?;
+    let tables = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut snapshot = Snapshot::new();
+    for (name, sql) in tables {
+        let columns = columns(conn, &name)?;
+        let rows = conn.query_row(&format!("SELECT COUNT(*) FROM \"{}\"", name), [], |row| {
+            row.get(0)
+        })?;
+        snapshot.insert(name, (sql, columns, rows));
+    }
+    Ok(snapshot)
+}
+
+/// Describes the differences between two snapshots, one line each.
+fn schema_changes(before: &Snapshot, after: &Snapshot) -> Vec<String> {
+    let mut changes = Vec::new();
+    for (table, (sql, columns, rows)) in after {
+        let (old_sql, old_columns, old_rows) = match before.get(table) {
+            Some(old) => old,
+            None => {
+                changes.push(format!("+ table {} ({})", table, columns.join(", ")));
+                if *rows > 0 {
+                    changes.push(format!("+ rows in {}: {}", table, rows));
+                }
+                continue;
+            }
+        };
+        for column in columns.iter().filter(|c| !old_columns.contains(c)) {
+            changes.push(format!("+ column {}.{}", table, column));
+        }
+        for column in old_columns.iter().filter(|c| !columns.contains(c)) {
+            changes.push(format!("- column {}.{}", table, column));
+        }
+        if columns == old_columns && sql != old_sql {
+            changes.push(format!("~ table {} rebuilt", table));
+        }
+        if rows != old_rows {
+            changes.push(format!("~ rows in {}: {} -> {}", table, old_rows, rows));
+        }
+    }
+    for table in before.keys().filter(|t| !after.contains_key(*t)) {
+        changes.push(format!("- table {}", table));
+    }
+    changes
+}
+
+/// Returns the column names of `table` in order.
+fn columns(conn: &Connection, table: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let cols = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    cols.collect()
+}
+
+/// 2.x -> 3.0: moves the feed URL out of `directories` into a `feeds`
+/// table and links watch-list and tracking rows to it. Pre-release 3.0
+/// databases that predate multi-feed get the same treatment.
+fn link_feeds(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS watchlist (
+            id     INTEGER PRIMARY KEY,
+            name   TEXT NOT NULL,
+            option TEXT NOT NULL);
+         CREATE TABLE IF NOT EXISTS item_tracker (
+            id     INTEGER PRIMARY KEY,
+            item   BLOB NOT NULL UNIQUE,
+            latest BLOB NOT NULL);
+         CREATE TABLE IF NOT EXISTS logs (
+            id        INTEGER PRIMARY KEY,
+            timestamp TEXT NOT NULL,
+            level     TEXT NOT NULL,
+            message   TEXT NOT NULL);",
+    )?;
+    if !watchlist_has_feed_id(conn)? {
+        backfill_feed_id(conn)?;
+    }
+    Ok(())
+}
+
+/// Returns true if the watchlist table already has a feed_id column.
+fn watchlist_has_feed_id(conn: &Connection) -> rusqlite::Result<bool> {
+    has_column(conn, "watchlist", "feed_id")
+}
+
+/// Returns true if `table` has a column named `column`.
+fn has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    Ok(columns(conn, table)?.iter().any(|c| c == column))
+}
+
+/// 3.0 -> 3.1: adds the per-feed scheduling columns. Existing feeds
+/// stay enabled and are checked on every run, matching the old behaviour.
+fn add_feed_schedule(conn: &Connection) -> rusqlite::Result<()> {
+    if !has_column(conn, "feeds", "enabled")? {
+        conn.execute(
+            "ALTER TABLE feeds ADD COLUMN enabled INTEGER NOT NULL DEFAULT 1",
+            [],
+        )?;
+    }
+    if !has_column(conn, "feeds", "interval_minutes")? {
+        conn.execute(
+            "ALTER TABLE feeds ADD COLUMN interval_minutes INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+    }
+    if !has_column(conn, "feeds", "last_checked")? {
+        conn.execute("ALTER TABLE feeds ADD COLUMN last_checked INTEGER", [])?;
+    }
+    Ok(())
+}
+
+/// Adds feed_id to an existing watchlist table and backfills every row
+/// to a "Default" feed, seeding that feed from the legacy `url` entry
+/// in `directories` if one doesn't already exist.
+fn backfill_feed_id(conn: &Connection) -> rusqlite::Result<()> {
+    // Ensure the feeds table exists.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS feeds (
+            id         INTEGER PRIMARY KEY,
+            name       TEXT NOT NULL UNIQUE,
+            url        TEXT NOT NULL,
+            is_default INTEGER NOT NULL DEFAULT 0)",
+        [],
+    )?;
+
+    // Reuse the existing default feed if one is already there (e.g. this
+    // is a re-run), otherwise seed one from the legacy directories.url.
+    let default_feed_id = match settings::get_default_feed_id(conn) {
+        Ok(id) => id,
+        Err(_) => {
+            let old_url = settings::get_stored_setting(conn, "url")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| String::from("https://nyaa.si/?page=rss"));
+            settings::db_write_feed(conn, "Default", &old_url, true)?;
+            settings::get_default_feed_id(conn)?
+        }
+    };
+
+    // SQLite requires a constant DEFAULT for a NOT NULL ADD COLUMN, so
+    // default to 0 and immediately overwrite with the real id.
+    conn.execute(
+        "ALTER TABLE watchlist ADD COLUMN feed_id INTEGER NOT NULL DEFAULT 0",
+        [],
+    )?;
+    conn.execute(
+        "UPDATE watchlist SET feed_id = ?1",
+        params![default_feed_id],
+    )?;
+
+    // `url` no longer lives in directories.
+    conn.execute("DELETE FROM directories WHERE option = 'url'", [])?;
+
+    let mut stmt = conn.prepare("PRAGMA table_info(item_tracker)")?;
+    let mut rows = stmt.query([])?;
+    let mut has_feed_id = false;
+
+    while let Some(row) = rows.next()? {
+        let col_name: String = row.get(1)?;
+        if col_name == "feed_id" {
+            has_feed_id = true;
+            break;
+        }
+    }
+
+    // If the legacy table doesn't have the column, migrate it inline
+    if !has_feed_id {
+        // Safe table-recreation pattern for SQLite composite constraints
+        conn.execute("ALTER TABLE item_tracker RENAME TO old_item_tracker;", [])?;
+
+        conn.execute(
+            "CREATE TABLE item_tracker (
+                id      INTEGER PRIMARY KEY,
+                item    BLOB NOT NULL,
+                latest  BLOB NOT NULL,
+                feed_id INTEGER NOT NULL DEFAULT 0,
+                UNIQUE(item, feed_id)
+            );",
+            [],
+        )?;
+
+        conn.execute(
+            "INSERT INTO item_tracker (id, item, latest, feed_id)
+             SELECT id, item, latest, ?1 FROM old_item_tracker;",
+            params![default_feed_id],
+        )?;
+
+        conn.execute("DROP TABLE old_item_tracker;", [])?;
+    }
+
+    Ok(())
+}
+
+/// 3.1 -> 3.2: adds the HTTP cache validator columns.
+fn add_feed_cache(conn: &Connection) -> rusqlite::Result<()> {
+    if !has_column(conn, "feeds", "etag")? {
+        conn.execute("ALTER TABLE feeds ADD COLUMN etag TEXT", [])?;
+    }
+    if !has_column(conn, "feeds", "last_modified")? {
+        conn.execute("ALTER TABLE feeds ADD COLUMN last_modified TEXT", [])?;
+    }
+    Ok(())
+}
+
+/// 3.2 -> 3.3: drops the UNIQUE constraint on `directories.path`. It
+/// dates from when the table only held paths, but numeric settings such
+/// as poll-interval and fetch-timeout can legitimately share a value.
+fn relax_directories_unique(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE directories_new (
+            option TEXT PRIMARY KEY,
+            path   TEXT NOT NULL);
+         INSERT INTO directories_new (option, path) SELECT option, path FROM directories;
+         DROP TABLE directories;
+         ALTER TABLE directories_new RENAME TO directories;",
+    )
+}
+
+/// 3.3 -> 3.4: adds the feed source columns. Existing feeds are
+/// plain RSS.
+fn add_feed_source(conn: &Connection) -> rusqlite::Result<()> {
+    if !has_column(conn, "feeds", "kind")? {
+        conn.execute(
+            "ALTER TABLE feeds ADD COLUMN kind TEXT NOT NULL DEFAULT 'rss'",
+            [],
+        )?;
+    }
+    if !has_column(conn, "feeds", "api_key")? {
+        conn.execute("ALTER TABLE feeds ADD COLUMN api_key TEXT", [])?;
+    }
+    if !has_column(conn, "feeds", "categories")? {
+        conn.execute("ALTER TABLE feeds ADD COLUMN categories TEXT", [])?;
+    }
+    Ok(())
+}
+
+/// 3.4 -> 3.5: adds a unix time to each log entry so old entries can be
+/// pruned. Existing entries get theirs from the formatted timestamp.
+fn add_log_times(conn: &Connection) -> rusqlite::Result<()> {
+    if !has_column(conn, "logs", "created_at")? {
+        conn.execute("ALTER TABLE logs ADD COLUMN created_at INTEGER", [])?;
+    }
+    let mut stmt = conn.prepare("SELECT id, timestamp FROM logs WHERE created_at IS NULL")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, timestamp) in rows {
+        if let Some(created_at) = settings::parse_log_time(&timestamp) {
+            conn.execute(
+                "UPDATE logs SET created_at = ?1 WHERE id = ?2",
+                params![created_at, id],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// 3.5 -> 3.6: adds the module and structured fields of each log entry.
+/// Existing entries have neither.
+fn add_log_source(conn: &Connection) -> rusqlite::Result<()> {
+    if !has_column(conn, "logs", "target")? {
+        conn.execute("ALTER TABLE logs ADD COLUMN target TEXT", [])?;
+    }
+    if !has_column(conn, "logs", "fields")? {
+        conn.execute("ALTER TABLE logs ADD COLUMN fields TEXT", [])?;
+    }
+    Ok(())
+}
+
+/// 3.6 -> 3.7: adds the download history, seeded from the "Downloaded"
+/// entries still in the logs table. Entries without a time are skipped.
+fn add_downloads(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS downloads (
+            id            INTEGER PRIMARY KEY,
+            downloaded_at INTEGER NOT NULL,
+            feed          TEXT,
+            watch_id      INTEGER,
+            item          TEXT,
+            title         TEXT NOT NULL,
+            url           TEXT)",
+        [],
+    )?;
+    conn.execute(
+        "INSERT INTO downloads (downloaded_at, feed, watch_id, item, title, url)
+         SELECT logs.created_at,
+                json_extract(logs.fields, '$.feed'),
+                json_extract(logs.fields, '$.watch_id'),
+                watchlist.name,
+                substr(logs.message, length('Downloaded ') + 1),
+                json_extract(logs.fields, '$.url')
+         FROM logs
+         LEFT JOIN watchlist ON watchlist.id = json_extract(logs.fields, '$.watch_id')
+         WHERE logs.message LIKE 'Downloaded %'
+           AND logs.message != 'Downloaded magnet link.'
+           AND logs.created_at IS NOT NULL
+         ORDER BY logs.id",
+        [],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(sql: &str) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(sql).unwrap();
+        conn
+    }
+
+    fn applied(conn: &Connection) -> Vec<u32> {
+        let mut stmt = conn
+            .prepare("SELECT version FROM schema_migrations ORDER BY version")
+            .unwrap();
+        let versions = stmt.query_map([], |row| row.get(0)).unwrap();
+        versions.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    /// Every table's columns, for comparing against a new database.
+    fn schema(conn: &Connection) -> Snapshot {
+        snapshot(conn)
+            .unwrap()
+            .into_iter()
+            .map(|(table, (_, cols, _))| (table, (String::new(), cols, 0)))
+            .collect()
+    }
+
+    fn new_database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        settings::db_create(&conn).unwrap();
+        settings::db_write_dir(&conn, "db-ver", settings::CURRENT_DB_VERSION).unwrap();
+        settings::get_db_ver(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn registry_ends_at_the_current_version() {
+        let last = MIGRATIONS.last().unwrap();
+        assert_eq!(last.db_ver, settings::CURRENT_DB_VERSION);
+        for (idx, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version as usize, idx + 1);
+        }
+        assert!(pending(&new_database()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn databases_from_2x_end_up_like_new_ones() {
+        let conn = fixture(include_str!("../tests/fixtures/db-2.0.sql"));
+        assert_eq!(settings::get_db_ver(&conn).unwrap(), MIGRATIONS.len());
+
+        assert_eq!(schema(&conn), schema(&new_database()));
+        assert_eq!(
+            applied(&conn),
+            (1..=MIGRATIONS.len() as u32).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            settings::get_stored_setting(&conn, "db-ver").unwrap(),
+            settings::CURRENT_DB_VERSION
+        );
+        assert_eq!(settings::get_stored_setting(&conn, "url").unwrap(), "");
+
+        let feeds = settings::read_feeds(&conn).unwrap();
+        assert_eq!(feeds.len(), 1);
+        assert_eq!(feeds[0].url, "https://nyaa.si/?page=rss&c=1_2&f=0");
+        assert!(feeds[0].is_default);
+        let watchlist = settings::read_watch_list(&conn).unwrap();
+        assert_eq!(watchlist.len(), 2);
+        assert!(watchlist.iter().all(|w| w.feed_id == feeds[0].id));
+        let tracked: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM item_tracker WHERE feed_id = ?1",
+                params![feeds[0].id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(tracked, 1);
+
+        // A second run has nothing left to do.
+        assert_eq!(settings::get_db_ver(&conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn prerelease_3_0_databases_are_linked_to_a_feed() {
+        let conn = fixture(include_str!("../tests/fixtures/db-3.0-prerelease.sql"));
+        settings::get_db_ver(&conn).unwrap();
+
+        assert_eq!(schema(&conn), schema(&new_database()));
+        let feeds = settings::read_feeds(&conn).unwrap();
+        assert_eq!(feeds[0].url, "https://nyaa.si/?page=rss&c=3_0&f=0");
+        assert_eq!(
+            settings::read_watch_list(&conn).unwrap()[0].feed_id,
+            feeds[0].id
+        );
+    }
+
+    #[test]
+    fn databases_from_3_0_keep_their_feeds() {
+        let conn = fixture(include_str!("../tests/fixtures/db-3.0.sql"));
+        assert_eq!(settings::get_db_ver(&conn).unwrap(), MIGRATIONS.len() - 1);
+
+        assert_eq!(schema(&conn), schema(&new_database()));
+        assert_eq!(
+            applied(&conn),
+            (1..=MIGRATIONS.len() as u32).collect::<Vec<_>>()
+        );
+        let feeds = settings::read_feeds(&conn).unwrap();
+        assert_eq!(
+            feeds
+                .iter()
+                .map(|f| (f.id, f.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(1, "Default"), (3, "Books")]
+        );
+        let watchlist = settings::read_watch_list(&conn).unwrap();
+        assert_eq!(
+            watchlist.iter().map(|w| w.feed_id).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+
+        let created: Vec<Option<i64>> = conn
+            .prepare("SELECT created_at FROM logs ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert!(created[0].is_some());
+        assert_eq!(created[1], None);
+
+        let history = settings::read_downloads(&conn, &Default::default()).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].title, "Spy x Family v01.cbz");
+        assert_eq!(Some(history[0].downloaded_at), created[0]);
+
+        // Settings may now share a value.
+        settings::db_write_dir(&conn, "poll-interval", "30").unwrap();
+        settings::db_write_dir(&conn, "fetch-timeout", "30").unwrap();
+    }
+
+    #[test]
+    fn dry_run_reports_without_changing_anything() {
+        let conn = fixture(include_str!("../tests/fixtures/db-2.0.sql"));
+        let before = snapshot(&conn).unwrap();
+
+        let reports = dry_run(&conn).unwrap();
+        assert_eq!(reports.len(), MIGRATIONS.len());
+        assert!(reports[0]
+            .changes
+            .contains(&String::from("+ column watchlist.feed_id")));
+        assert!(reports[1]
+            .changes
+            .contains(&String::from("+ column feeds.enabled")));
+        assert!(reports[3]
+            .changes
+            .contains(&String::from("~ table directories rebuilt")));
+
+        assert_eq!(snapshot(&conn).unwrap(), before);
+        let has_table: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'schema_migrations'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(has_table, 0);
+    }
+}
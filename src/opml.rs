@@ -0,0 +1,252 @@
+use crate::settings;
+use crate::settings::{Feed, FeedKind};
+use anyhow::{anyhow, Result};
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
+use rusqlite::Connection;
+use std::fs;
+
+/// Namespace used for the attributes OPML has no equivalent for.
+const NYAADLE_NS: &str = "https://github.com/AJigsawnHalo/Nyaadle";
+
+/// What to do when an imported feed has the same name as an existing one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Conflict {
+    /// Keep the existing feed and ignore the imported one.
+    Skip,
+    /// Add the imported feed under a numbered name, e.g. `Books (2)`.
+    Rename,
+    /// Point the existing feed at the imported URL.
+    Replace,
+}
+
+impl Conflict {
+    pub fn parse(value: &str) -> Option<Conflict> {
+        match value {
+            "skip" => Some(Conflict::Skip),
+            "rename" => Some(Conflict::Rename),
+            "replace" => Some(Conflict::Replace),
+            _ => None,
+        }
+    }
+}
+
+/// A feed read from an OPML outline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpmlFeed {
+    pub name: String,
+    pub url: String,
+    pub kind: FeedKind,
+    pub is_default: bool,
+}
+
+/// Writes every feed to `path` as an OPML 2.0 document.
+pub fn export(conn: &Connection, path: &str) -> Result<usize> {
+    let feeds = settings::read_feeds(conn)?;
+    fs::write(path, write_opml(&feeds))?;
+    Ok(feeds.len())
+}
+
+/// Adds the feeds listed in the OPML file at `path`, resolving name clashes
+/// with `on_conflict`. Feeds whose URL is already present are skipped.
+/// Returns the number of feeds added or replaced.
+pub fn import(conn: &Connection, path: &str, on_conflict: Conflict) -> Result<usize> {
+    let content = fs::read_to_string(path)?;
+    let mut imported = 0;
+
+    for entry in read_opml(&content)? {
+        let feeds = settings::read_feeds(conn)?;
+        if let Some(existing) = feeds.iter().find(|f| f.url == entry.url) {
+            println!(
+                "Skipped \"{}\": already added as \"{}\".",
+                entry.name, existing.name
+            );
+            continue;
+        }
+
+        let name = match feeds.iter().find(|f| f.name == entry.name) {
+            None => entry.name.clone(),
+            Some(_) if on_conflict == Conflict::Skip => {
+                println!("Skipped \"{}\": a feed with that name exists.", entry.name);
+                continue;
+            }
+            Some(existing) if on_conflict == Conflict::Replace => {
+                settings::update_feed_url(conn, &existing.name, &entry.url)?;
+                existing.name.clone()
+            }
+            Some(_) => unique_name(&entry.name, &feeds),
+        };
+
+        // A replaced feed keeps its Torznab key and categories.
+        let current = feeds.iter().find(|f| f.name == name);
+        if current.is_none() {
+            settings::db_write_feed(conn, &name, &entry.url, false)?;
+        }
+        settings::set_feed_source(
+            conn,
+            &name,
+            entry.kind,
+            current.and_then(|f| f.api_key.as_deref()),
+            current.and_then(|f| f.categories.as_deref()),
+        )?;
+        if entry.is_default {
+            settings::set_default_feed(conn, &name)?;
+        }
+        println!("Imported \"{}\" | {}", name, entry.url);
+        imported += 1;
+    }
+
+    info!("Imported {} feeds from {}.", imported, path);
+    Ok(imported)
+}
+
+/// Returns `name` with the lowest ` (n)` suffix that isn't taken.
+fn unique_name(name: &str, feeds: &[Feed]) -> String {
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !feeds.iter().any(|f| &f.name == candidate))
+        .expect("Ran out of feed names.")
+}
+
+/// Renders feeds as an OPML document. The default flag and feed type are
+/// stored in `nyaadle:` attributes that other readers ignore.
+pub fn write_opml(feeds: &[Feed]) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<opml version=\"2.0\" xmlns:nyaadle=\"{}\">\n",
+        NYAADLE_NS
+    ));
+    out.push_str("  <head>\n    <title>Nyaadle feeds</title>\n  </head>\n  <body>\n");
+    for feed in feeds {
+        let name = escape(feed.name.as_str());
+        out.push_str(&format!(
+            "    <outline type=\"rss\" text=\"{}\" title=\"{}\" xmlUrl=\"{}\"",
+            name,
+            name,
+            escape(feed.url.as_str())
+        ));
+        if feed.kind != FeedKind::Rss {
+            out.push_str(&format!(" nyaadle:kind=\"{}\"", feed.kind.as_str()));
+        }
+        if feed.is_default {
+            out.push_str(" nyaadle:default=\"true\"");
+        }
+        out.push_str("/>\n");
+    }
+    out.push_str("  </body>\n</opml>\n");
+    out
+}
+
+/// Reads every outline with an `xmlUrl` from an OPML document. Folders are
+/// flattened, and outlines without a name fall back to their URL.
+pub fn read_opml(content: &str) -> Result<Vec<OpmlFeed>> {
+    let mut reader = Reader::from_str(content);
+    let mut feeds = Vec::new();
+    let mut seen_opml = false;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"opml" => seen_opml = true,
+                b"outline" => {
+                    if let Some(feed) = read_outline(&e)? {
+                        feeds.push(feed);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !seen_opml {
+        return Err(anyhow!("not an OPML document"));
+    }
+    Ok(feeds)
+}
+
+fn read_outline(outline: &BytesStart) -> Result<Option<OpmlFeed>> {
+    let mut url = None;
+    let mut text = None;
+    let mut title = None;
+    let mut kind = FeedKind::Rss;
+    let mut is_default = false;
+
+    for attr in outline.attributes() {
+        let attr = attr?;
+        let value = attr.normalized_value(XmlVersion::Implicit1_0)?.into_owned();
+        match attr.key.as_ref() {
+            b"xmlUrl" => url = Some(value),
+            b"text" => text = Some(value),
+            b"title" => title = Some(value),
+            b"nyaadle:kind" => kind = FeedKind::parse(&value).unwrap_or(FeedKind::Rss),
+            b"nyaadle:default" => is_default = value == "true",
+            _ => {}
+        }
+    }
+
+    Ok(url.filter(|u| !u.is_empty()).map(|url| OpmlFeed {
+        name: text
+            .or(title)
+            .filter(|n| !n.trim().is_empty())
+            .unwrap_or_else(|| url.clone()),
+        url,
+        kind,
+        is_default,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exported_feeds_read_back_the_same() {
+        let mut books = Feed::temporary(String::from("https://nyaa.si/?page=rss&c=3_0&f=0"));
+        books.name = String::from("Books & Manga");
+        books.is_default = true;
+        let mut search = Feed::temporary(String::from("https://nyaa.si/?page=rss&q={query}"));
+        search.name = String::from("Search");
+        search.kind = FeedKind::Search;
+
+        let feeds = read_opml(&write_opml(&[books, search])).unwrap();
+        assert_eq!(
+            feeds,
+            vec![
+                OpmlFeed {
+                    name: String::from("Books & Manga"),
+                    url: String::from("https://nyaa.si/?page=rss&c=3_0&f=0"),
+                    kind: FeedKind::Rss,
+                    is_default: true,
+                },
+                OpmlFeed {
+                    name: String::from("Search"),
+                    url: String::from("https://nyaa.si/?page=rss&q={query}"),
+                    kind: FeedKind::Search,
+                    is_default: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_outlines_from_other_readers_are_flattened() {
+        let opml = r#"<?xml version="1.0"?>
+<opml version="1.0">
+  <body>
+    <outline text="Anime">
+      <outline text="Subs" type="rss" xmlUrl="https://example.com/subs.rss"/>
+      <outline type="rss" xmlUrl="https://example.com/raws.rss"/>
+    </outline>
+  </body>
+</opml>"#;
+        let feeds = read_opml(opml).unwrap();
+        assert_eq!(feeds.len(), 2);
+        assert_eq!(feeds[0].name, "Subs");
+        assert_eq!(feeds[1].name, "https://example.com/raws.rss");
+        assert!(read_opml("<rss/>").is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use serde_json::{Map, Value as Json};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// How lists and check results are printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// The human-readable text each command has always printed.
+    Table,
+    /// A JSON array with one object per row.
+    Json,
+    /// Tab-separated values with a header line.
+    Tsv,
+    /// Comma-separated values with a header line.
+    Csv,
+}
+
+impl Format {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Format::Table => "table",
+            Format::Json => "json",
+            Format::Tsv => "tsv",
+            Format::Csv => "csv",
+        }
+    }
+
+    pub fn parse(format: &str) -> Option<Format> {
+        match format {
+            "table" => Some(Format::Table),
+            "json" => Some(Format::Json),
+            "tsv" => Some(Format::Tsv),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Format picked for this run by `set_format`.
+static FORMAT: OnceLock<Format> = OnceLock::new();
+
+/// Sets the output format for this run. Only the first call has an effect.
+pub fn set_format(format: Format) {
+    let _ = FORMAT.set(format);
+}
+
+/// Returns the output format for this run, table unless set otherwise.
+pub fn format() -> Format {
+    FORMAT.get().copied().unwrap_or(Format::Table)
+}
+
+/// Whether `status!` prints anything. Off unless turned on, so programs
+/// using nyaadle as a library don't get its progress messages.
+static SHOW_STATUS: AtomicBool = AtomicBool::new(false);
+
+/// Turns progress messages on or off. The command-line program turns
+/// them on at startup.
+pub fn show_status(show: bool) {
+    SHOW_STATUS.store(show, Ordering::Relaxed);
+}
+
+/// Returns true if progress messages are printed.
+pub fn status_shown() -> bool {
+    SHOW_STATUS.load(Ordering::Relaxed)
+}
+
+/// Prints a progress message. It goes to stdout normally, and to stderr
+/// when a machine-readable format is used so stdout holds only the data.
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::output::status_shown() {
+            if $crate::output::format() == $crate::output::Format::Table {
+                println!($($arg)*);
+            } else {
+                eprintln!($($arg)*);
+            }
+        }
+    };
+}
+
+/// Rows of named columns, rendered in any `Format`.
+pub struct Output {
+    /// Column keys, used as JSON keys and TSV/CSV headers.
+    keys: Vec<&'static str>,
+    /// Column titles for the table format.
+    headings: Vec<&'static str>,
+    rows: Vec<Vec<Json>>,
+}
+
+impl Output {
+    /// Starts an empty output with `(key, heading)` columns.
+    pub fn new(columns: &[(&'static str, &'static str)]) -> Output {
+        Output {
+            keys: columns.iter().map(|(key, _)| *key).collect(),
+            headings: columns.iter().map(|(_, heading)| *heading).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Adds a row, one value per column.
+    pub fn row(&mut self, cells: Vec<Json>) {
+        debug_assert_eq!(cells.len(), self.keys.len());
+        self.rows.push(cells);
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Table => self.render_delimited(&self.headings, " | ", table_cell),
+            Format::Json => self.render_json(),
+            Format::Tsv => self.render_delimited(&self.keys, "\t", tsv_cell),
+            Format::Csv => self.render_delimited(&self.keys, ",", csv_cell),
+        }
+    }
+
+    fn render_json(&self) -> String {
+        let rows: Vec<Json> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let object: Map<String, Json> = self
+                    .keys
+                    .iter()
+                    .map(|key| key.to_string())
+                    .zip(row.iter().cloned())
+                    .collect();
+                Json::Object(object)
+            })
+            .collect();
+        let mut out = serde_json::to_string_pretty(&rows).unwrap_or_default();
+        out.push('\n');
+        out
+    }
+
+    fn render_delimited(
+        &self,
+        header: &[&str],
+        separator: &str,
+        cell: fn(&Json) -> String,
+    ) -> String {
+        let mut out = header.join(separator);
+        out.push('\n');
+        for row in &self.rows {
+            let cells: Vec<String> = row.iter().map(cell).collect();
+            out.push_str(&cells.join(separator));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Formats a unix time as RFC 3339 in UTC, for machine-readable output.
+pub fn utc_time(timestamp: i64) -> String {
+    OffsetDateTime::from_unix_timestamp(timestamp)
+        .ok()
+        .and_then(|dt| dt.format(&Rfc3339).ok())
+        .unwrap_or_default()
+}
+
+/// A value as plain text, with nothing for null.
+fn plain(value: &Json) -> String {
+    match value {
+        Json::Null => String::new(),
+        Json::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn table_cell(value: &Json) -> String {
+    match value {
+        Json::Null => String::from("-"),
+        other => plain(other),
+    }
+}
+
+/// TSV has no quoting, so tabs and line breaks become spaces.
+fn tsv_cell(value: &Json) -> String {
+    plain(value).replace(['\t', '\n', '\r'], " ")
+}
+
+/// Quotes a CSV field if it contains a comma, quote or line break.
+fn csv_cell(value: &Json) -> String {
+    let field = plain(value);
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Output {
+        let mut out = Output::new(&[("id", "ID"), ("title", "Item Title"), ("feed", "Feed")]);
+        out.row(vec![
+            Json::from(1),
+            Json::from("Frieren, \"S01\"\t07"),
+            Json::Null,
+        ]);
+        out
+    }
+
+    #[test]
+    fn every_format_renders_the_same_rows() {
+        let out = sample();
+        assert_eq!(
+            out.render(Format::Table),
+            "ID | Item Title | Feed\n1 | Frieren, \"S01\"\t07 | -\n"
+        );
+        assert_eq!(
+            out.render(Format::Tsv),
+            "id\ttitle\tfeed\n1\tFrieren, \"S01\" 07\t\n"
+        );
+        assert_eq!(
+            out.render(Format::Csv),
+            "id,title,feed\n1,\"Frieren, \"\"S01\"\"\t07\",\n"
+        );
+
+        let json: Json = serde_json::from_str(&out.render(Format::Json)).unwrap();
+        assert_eq!(json[0]["id"], 1);
+        assert_eq!(json[0]["title"], "Frieren, \"S01\"\t07");
+        assert_eq!(json[0]["feed"], Json::Null);
+        let keys: Vec<&String> = json[0].as_object().unwrap().keys().collect();
+        assert_eq!(keys, vec!["id", "title", "feed"]);
+    }
+
+    #[test]
+    fn formats_round_trip_through_their_names() {
+        for format in [Format::Table, Format::Json, Format::Tsv, Format::Csv] {
+            assert_eq!(Format::parse(format.as_str()), Some(format));
+        }
+        assert_eq!(Format::parse("xml"), None);
+    }
+}
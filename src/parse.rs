@@ -1,15 +1,24 @@
 // Parts of this code was adapted from "The Rust Cookbook"
 // which can be found at: https://rust-lang-nursery.github.io/rust-cookbook/
 
+use crate::error::{Error, Result};
 use crate::settings;
-use crate::settings::Watchlist;
-use anyhow::Result;
+use crate::settings::{FeedKind, Watchlist};
+use crate::status;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
 use rss::Channel;
 use rusqlite::Connection;
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::copy;
 use std::io::Cursor;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 
 #[cfg(feature = "discord")]
 use serenity::builder::ExecuteWebhook;
@@ -21,22 +30,16 @@ use serenity::model::webhook::Webhook;
 /// Extracts the filename from a URL string without making a network request.
 fn filename_from_url(url: &str) -> &str {
     url.split('/')
-        .last()
+        .next_back()
         .filter(|s| !s.is_empty())
         .unwrap_or("tmp.bin")
 }
 
 /// Checks if the target has already been downloaded and archived.
 /// Returns 0 if found (skip), 1 if not found (proceed).
-async fn archive_check(
-    target: &str,
-    archive_dir: &str,
-    force: bool,
-    conn: &Connection,
-) -> Result<u8> {
+async fn archive_check(target: &str, archive_dir: &str, force: bool) -> Result<u8> {
     if force {
         warn!("Force option enabled.");
-        let _ = settings::write_log(conn, "WARN", "Force option enabled");
         return Ok(1);
     }
     let path = Path::new(archive_dir).join(filename_from_url(target));
@@ -49,74 +52,84 @@ async fn downloader(conn: &Connection, target: &str, title: &str, force: bool) -
     let dl_dir = settings::get_settings(conn, "dl-dir")?;
     let archive_dir = settings::get_settings(conn, "ar-dir")?;
 
-    #[cfg(feature = "discord")]
-    let wbhk_url = settings::get_settings(conn, "webhk_url").expect("No webhook url set");
-    #[cfg(feature = "discord")]
-    let http = Http::new("");
-    #[cfg(feature = "discord")]
-    let wbhk = Webhook::from_url(&http, &wbhk_url)
-        .await
-        .expect("Failed to get webhook url.");
-
     if !Path::new(&dl_dir).exists() {
-        std::fs::create_dir_all(&dl_dir).expect("Failed to create download directory");
+        std::fs::create_dir_all(&dl_dir).map_err(Error::fs("create", &dl_dir))?;
     }
     if !Path::new(&archive_dir).exists() {
-        std::fs::create_dir_all(&archive_dir).expect("Failed to create archive directory");
+        std::fs::create_dir_all(&archive_dir).map_err(Error::fs("create", &archive_dir))?;
     }
 
-    match archive_check(target, &archive_dir, force, conn).await? {
+    match archive_check(target, &archive_dir, force).await? {
         1 => {
-            let response = reqwest::get(target).await?;
+            // An error page must not be saved and archived as the file.
+            let response = reqwest::get(target).await?.error_for_status()?;
 
             let fname = response
                 .url()
                 .path_segments()
-                .and_then(|segments| segments.last())
+                .and_then(|mut segments| segments.next_back())
                 .and_then(|name| if name.is_empty() { None } else { Some(name) })
                 .unwrap_or("tmp.bin");
 
-            println!("file to download: '{}'", fname);
+            status!("file to download: '{}'", fname);
 
             let dest_name = format!("{}/{}", dl_dir, fname);
             let archive_name = format!("{}/{}", archive_dir, fname);
 
-            println!("will be located under: '{}'", dest_name);
+            status!("will be located under: '{}'", dest_name);
 
-            let mut dest = File::create(&dest_name)?;
+            let mut dest = File::create(&dest_name).map_err(Error::fs("create", &dest_name))?;
             let mut content = Cursor::new(response.bytes().await?);
-            copy(&mut content, &mut dest)?;
+            copy(&mut content, &mut dest).map_err(Error::fs("write", &dest_name))?;
 
             // Copy to archive so future cron runs can detect it by filename
-            let mut dest2 = File::open(&dest_name)?;
-            let mut archive = File::create(archive_name)?;
-            copy(&mut dest2, &mut archive)?;
-
-            info!("Downloaded {}", title);
-            let _ = settings::write_log(conn, "INFO", &format!("Downloaded {}", title));
+            let mut dest2 = File::open(&dest_name).map_err(Error::fs("read", &dest_name))?;
+            let mut archive =
+                File::create(&archive_name).map_err(Error::fs("create", &archive_name))?;
+            copy(&mut dest2, &mut archive).map_err(Error::fs("write", &archive_name))?;
+            debug!("Saved {} to {}", title, dest_name);
 
+            // The file is already saved, so a broken webhook only warrants a warning.
             #[cfg(feature = "discord")]
             {
-                let content = format!("Downloaded {}", title);
-                let builder = ExecuteWebhook::new().content(content).username("Nyaadle");
-                wbhk.execute(&http, false, builder)
-                    .await
-                    .expect("Failed to execute webhook.");
+                if let Err(e) = notify(conn, title).await {
+                    warn!("{}", e);
+                }
             }
 
             Ok(1)
         }
         _ => {
-            println!("File Found. Skipping Download.");
+            status!("File Found. Skipping Download.");
             Ok(0)
         }
     }
 }
 
+/// Posts a message about a finished download to the Discord webhook.
+#[cfg(feature = "discord")]
+async fn notify(conn: &Connection, title: &str) -> Result<()> {
+    let wbhk_url = settings::get_settings(conn, "webhk_url")?;
+    if wbhk_url.is_empty() {
+        return Err(Error::Notifier(String::from("no webhook URL is set")));
+    }
+    let http = Http::new("");
+    let wbhk = Webhook::from_url(&http, &wbhk_url)
+        .await
+        .map_err(|e| Error::Notifier(e.to_string()))?;
+    let content = format!("Downloaded {}", title);
+    let builder = ExecuteWebhook::new().content(content).username("Nyaadle");
+    wbhk.execute(&http, false, builder)
+        .await
+        .map_err(|e| Error::Notifier(e.to_string()))?;
+    Ok(())
+}
+
 /// Downloads a list of URLs directly, bypassing the watchlist/feed logic.
-pub async fn arg_dl(conn: &Connection, links: Vec<String>) -> Result<()> {
+/// A link that fails is logged and counted, and the rest are still tried.
+pub async fn arg_dl(conn: &Connection, links: Vec<String>) -> Result<Summary> {
     info!("Nyaadle started in download mode.");
-    let _ = settings::write_log(conn, "INFO", "Nyaadle started in download mode.");
+    let mut summary = Summary::default();
     let mut num_dl = 0;
 
     for link in links.iter() {
@@ -124,21 +137,37 @@ pub async fn arg_dl(conn: &Connection, links: Vec<String>) -> Result<()> {
             break;
         }
         // Pass 0 as the default feed_id here
-        if !tracking_check(conn, link.to_string(), link, true, 0) {
+        if !tracking_check(conn, link, link, true, 0)? {
             if link.contains("magnet:") {
                 match opener::open(link) {
                     Ok(_) => {
-                        println!("Opening magnet link...");
+                        status!("Opening magnet link...");
                         num_dl += 1;
+                        settings::update_tracking(conn, link, link, 0)?;
+                        record_download(conn, None, None, link, link);
                     }
-                    Err(_) => println!("Error. Path not found."),
+                    Err(_) => status!("Error. Path not found."),
                 }
-                info!("Downloaded magnet link.");
-                let _ = settings::write_log(conn, "INFO", "Downloaded magnet link.");
+                info!(event = "download", url = link.as_str(); "Downloaded magnet link.");
             } else {
-                match downloader(conn, link, link, true).await? {
-                    1 => num_dl += 1,
-                    _ => {}
+                let started = Instant::now();
+                match downloader(conn, link, link, true).await {
+                    Ok(1) => {
+                        num_dl += 1;
+                        settings::update_tracking(conn, link, link, 0)?;
+                        record_download(conn, None, None, link, link);
+                        info!(
+                            event = "download",
+                            url = link.as_str(),
+                            duration_ms = started.elapsed().as_millis() as u64;
+                            "Downloaded {}", link
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        summary.failed_downloads += 1;
+                        download_failed(None, link, &e);
+                    }
                 }
             }
         }
@@ -148,59 +177,106 @@ pub async fn arg_dl(conn: &Connection, links: Vec<String>) -> Result<()> {
         debug!("No items downloaded. Nyaadle closed.");
     } else {
         info!("{} items downloaded. Nyaadle closed.", num_dl);
-        let _ = settings::write_log(
-            conn,
-            "INFO",
-            &format!("{} items downloaded. Nyaadle closed.", num_dl),
-        );
     }
-    Ok(())
+    summary.downloaded = num_dl;
+    Ok(summary)
 }
 
-/// Checks the tracking table and updates it. Returns true if already downloaded.
-fn tracking_check(conn: &Connection, item: String, wl_title: &str, force: bool, feed_id: i32) -> bool {
-    let trck = settings::get_tracking(conn, wl_title, feed_id).expect("Failed to get tracking.");
+/// Logs and prints a download that failed, so the run can move on.
+pub fn download_failed(feed: Option<&str>, title: &str, e: &Error) {
+    error!(
+        event = "download_error",
+        feed = feed.unwrap_or_default(),
+        title = title;
+        "Failed to download {}: {}", title, e
+    );
+    status!("Failed to download {}: {}", title, e);
+    if let Some(hint) = e.hint() {
+        status!("{}", hint);
+    }
+}
+
+/// Checks the tracking table. Returns true if already downloaded.
+/// The caller updates it once the item has been handled, so a failed
+/// download is tried again on the next run.
+fn tracking_check(
+    conn: &Connection,
+    item: &str,
+    wl_title: &str,
+    force: bool,
+    feed_id: i32,
+) -> Result<bool> {
+    let trck = settings::get_tracking(conn, wl_title, feed_id)?;
     if trck == item && !force {
-        println!("Item already downloaded. Skipping...");
-        true
+        status!("Item already downloaded. Skipping...");
+        Ok(true)
     } else {
-        let _ = settings::update_tracking(conn, wl_title, &item, feed_id).is_ok();
-        false
+        Ok(false)
     }
 }
 
-/// Resolves the link from an RSS item and dispatches to downloader.
-async fn download_logic(
+/// Resolves the link from an RSS item matched by `entry` and dispatches
+/// to downloader. `feed` is the name of the feed the item came from.
+pub async fn download_logic(
     conn: &Connection,
     item: &rss::Item,
-    wl_title: &str,
+    entry: &Watchlist,
+    feed: &str,
     force: bool,
-    feed_id: i32,
 ) -> Result<u8> {
-    let title = item.title().expect("Failed to extract title");
+    let title = item
+        .title()
+        .ok_or_else(|| Error::Feed(format!("an item in {} has no title", feed)))?;
 
-    if tracking_check(conn, title.to_string(), wl_title, force, feed_id) {
+    if tracking_check(conn, title, &entry.title, force, entry.feed_id)? {
         return Ok(0);
     }
-    println!("Downloading {}", title);
+    status!("Downloading {}", title);
 
     let target = match item.link() {
         Some(link) => link,
-        None => return Ok(0),
+        None => {
+            settings::update_tracking(conn, &entry.title, title, entry.feed_id)?;
+            return Ok(0);
+        }
     };
 
-    if target.contains("magnet:") {
-        info!("Downloaded {}", title);
-        let _ = settings::write_log(conn, "INFO", &format!("Downloaded {}", title));
+    let started = Instant::now();
+    let downloaded = if target.contains("magnet:") {
         match opener::open(target) {
-            Ok(_) => Ok(1),
-            Err(_) => Ok(0),
+            Ok(_) => 1,
+            Err(_) => 0,
         }
     } else {
-        match downloader(conn, target, title, force).await? {
-            1 => Ok(1),
-            _ => Ok(0),
-        }
+        downloader(conn, target, title, force).await?
+    };
+    settings::update_tracking(conn, &entry.title, title, entry.feed_id)?;
+    if downloaded == 1 {
+        record_download(conn, Some(feed), Some(entry), title, target);
+        info!(
+            event = "download",
+            feed = feed,
+            watch_id = entry.id,
+            title = title,
+            url = target,
+            duration_ms = started.elapsed().as_millis() as u64;
+            "Downloaded {}", title
+        );
+    }
+    Ok(downloaded)
+}
+
+/// Adds a download to the history. A failure is logged rather than
+/// returned, since the file has already been fetched.
+fn record_download(
+    conn: &Connection,
+    feed: Option<&str>,
+    entry: Option<&Watchlist>,
+    title: &str,
+    url: &str,
+) {
+    if let Err(e) = settings::write_download(conn, feed, entry, title, url) {
+        warn!("Failed to add {} to the download history: {}", title, e);
     }
 }
 
@@ -210,21 +286,80 @@ pub async fn arg_parse(
     feed: Option<String>,
     item: Option<String>,
     vid_opt: Option<String>,
-) -> Result<()> {
-    match (&item, &vid_opt) {
-        (Some(title), Some(opt)) => {
-            println!("Parsing for: '{}' with option '{}'", title, opt);
-            feed_parser(conn, false, force, feed, Some(title.clone()), Some(opt.clone())).await?;
-        }
-        (Some(_), None) | (None, Some(_)) => {
-            println!("Both --title and --option are required together.");
-            std::process::exit(0);
-        }
-        (None, None) => {
-            feed_parser(conn, false, force, feed, None, None).await?;
+) -> Result<Summary> {
+    if let (Some(title), Some(opt)) = (&item, &vid_opt) {
+        status!("Parsing for: '{}' with option '{}'", title, opt);
+    }
+    feed_parser(conn, false, force, feed, item, vid_opt).await
+}
+
+/// An item a `--check` run looked at for a watch-list entry, because its
+/// title contains the entry's title, and what a real run would do with it.
+#[derive(Clone, Debug)]
+pub struct Match {
+    pub feed: String,
+    pub watch_id: i32,
+    /// Title and download option of the watch-list entry.
+    pub item: String,
+    pub option: String,
+    pub title: String,
+    pub url: Option<String>,
+    /// Why the item would be left alone, or `None` if it would be
+    /// downloaded.
+    pub skip: Option<Skip>,
+    /// Where the file would be saved. `None` for magnet links, which are
+    /// handed to the system's torrent client.
+    pub destination: Option<String>,
+}
+
+impl Match {
+    pub fn would_download(&self) -> bool {
+        self.skip.is_none()
+    }
+}
+
+/// Why a run would not download an item whose title matched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Skip {
+    /// The watch-list entry has no download option set.
+    NoOption,
+    /// The download option doesn't appear in the item's title.
+    OptionMismatch,
+    /// The item has no link to download.
+    NoLink,
+    /// It's the last item downloaded for this entry.
+    Tracked,
+    /// A file with the same name is already in `ar-dir`.
+    Archived,
+}
+
+impl Skip {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Skip::NoOption => "no_option",
+            Skip::OptionMismatch => "option_mismatch",
+            Skip::NoLink => "no_link",
+            Skip::Tracked => "tracked",
+            Skip::Archived => "archived",
         }
     }
-    Ok(())
+}
+
+/// What a run over the feeds did.
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    /// Feeds that were checked.
+    pub feeds: u32,
+    /// Feeds with at least one request that couldn't be fetched or parsed,
+    /// or whose items couldn't be processed.
+    pub failed: u32,
+    pub downloaded: u32,
+    /// Matched items that couldn't be downloaded. Each is logged and the
+    /// run carries on with the next one.
+    pub failed_downloads: u32,
+    /// Items a `--check` run looked at, whether it would download them
+    /// or not.
+    pub found: Vec<Match>,
 }
 
 /// Fetches and parses the RSS feed then runs the main logic.
@@ -236,7 +371,7 @@ pub async fn feed_parser(
     feed_url: Option<String>,
     item_title: Option<String>,
     vid_opt: Option<String>,
-) -> Result<()> {
+) -> Result<Summary> {
     let master_watchlist = if let (Some(t), Some(o)) = (item_title, vid_opt) {
         vec![settings::Watchlist {
             id: -1,
@@ -245,30 +380,99 @@ pub async fn feed_parser(
             feed_id: -1,
         }]
     } else {
-        settings::read_watch_list(conn).unwrap_or_default()
+        settings::read_watch_list(conn)?
     };
 
     if master_watchlist.is_empty() || master_watchlist.iter().all(|w| w.title.is_empty()) {
         warn!("Watch-list not found.");
-        let _ = settings::write_log(conn, "WARN", "Watch-list not found.");
-        println!("Please set a watch-list by running 'nyaadle wle --add'");
-        return Ok(());
+        status!("Please set a watch-list by running 'nyaadle wle --add'");
+        return Ok(Summary::default());
     }
 
+    // A check doesn't download anything, so it can overlap another run.
+    let _lock = if check {
+        None
+    } else {
+        let lock_file = settings::lock_path(conn).unwrap_or_default();
+        match settings::try_run_lock(conn).map_err(Error::fs("lock", &lock_file))? {
+            Some(lock) => Some(lock),
+            None => {
+                warn!("Another run is in progress. Skipping this run.");
+                status!(
+                    "Another nyaadle run is already checking feeds (lock file: {}). Skipping this run.",
+                    lock_file.display()
+                );
+                return Ok(Summary::default());
+            }
+        }
+    };
+
     let feeds = if let Some(url) = feed_url {
-        vec![settings::Feed {
-            id: -1,
-            name: "Temporary Feed".to_string(),
-            url,
-            is_default: false,
-        }]
+        vec![settings::Feed::temporary(url)]
     } else {
-        settings::read_feeds(conn).unwrap_or_default()
+        // A check explains what a run would do with the feed's items, so it
+        // looks at every enabled feed, due or not.
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        settings::read_feeds(conn)?
+            .into_iter()
+            .filter(|feed| {
+                let due = if check {
+                    feed.enabled
+                } else {
+                    feed.is_due(now)
+                };
+                if !due {
+                    debug!("Skipping feed {}: disabled or not yet due.", feed.name);
+                }
+                due
+            })
+            .collect()
     };
 
-    let mut total_downloaded: u32 = 0;
+    let started = Instant::now();
+    let summary = parse_feeds(conn, feeds, &master_watchlist, check, force).await;
+    let total_downloaded = summary.downloaded;
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    if total_downloaded == 0 {
+        debug!(
+            event = "run",
+            downloaded = 0,
+            duration_ms = duration_ms;
+            "Nyaadle finished. No new items downloaded."
+        );
+    } else {
+        info!(
+            event = "run",
+            downloaded = total_downloaded,
+            duration_ms = duration_ms;
+            "Nyaadle finished. Total items downloaded: {}", total_downloaded
+        );
+    }
+
+    Ok(summary)
+}
 
-    for feed in feeds {
+/// Fetches each of the given feeds and runs the main logic against the
+/// watch-list entries linked to it. Returns how many feeds were checked and
+/// failed and how many items were downloaded, or with `check`, the matches.
+///
+/// Feeds are fetched concurrently, at most `fetch-limit` at a time and each
+/// bounded by `fetch-timeout`, so one slow mirror doesn't hold up the rest.
+/// Matching and downloading then run one feed at a time in the order the
+/// feeds were given, keeping tracking writes on the shared connection
+/// deterministic. A feed that fails to fetch or parse is logged and skipped.
+pub async fn parse_feeds(
+    conn: &Connection,
+    feeds: Vec<settings::Feed>,
+    master_watchlist: &[Watchlist],
+    check: bool,
+    force: bool,
+) -> Summary {
+    let mut summary = Summary::default();
+
+    let mut pending: Vec<(settings::Feed, Vec<Watchlist>)> = Vec::new();
+    for mut feed in feeds {
         let mut local_watchlist: Vec<Watchlist> = master_watchlist
             .iter()
             .filter(|item| feed.id == -1 || item.feed_id == feed.id || item.id == -1)
@@ -281,56 +485,479 @@ pub async fn feed_parser(
             }
         }
 
-        if local_watchlist.is_empty() {
-            continue;
+        // A one-off `parse -t` entry isn't the real watch-list, so it must
+        // see the whole feed and leave the feed's state alone. A check needs
+        // the whole feed too, since a 304 would leave it nothing to plan.
+        let one_off = local_watchlist.iter().any(|item| item.id == -1);
+        if one_off || check {
+            feed.etag = None;
+            feed.last_modified = None;
         }
 
-        let content = match reqwest::get(&feed.url).await {
-            Ok(res) => match res.bytes().await {
-                Ok(bytes) => bytes,
-                Err(e) => {
-                    error!("Failed to read bytes from feed {}: {}", feed.name, e);
-                    continue;
+        // Record the attempt before fetching, so a feed that then fails
+        // still waits out its interval instead of being retried every run.
+        // A --check run downloads nothing, so it doesn't use up the interval.
+        if feed.id != -1 && !check && !one_off {
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            let _ = settings::update_last_checked(conn, feed.id, now);
+        }
+
+        if !local_watchlist.is_empty() {
+            pending.push((feed, local_watchlist));
+        }
+    }
+
+    // Most feeds are a single request, but query-based feeds expand into
+    // one request per watch-list entry. Each request remembers which feed
+    // it belongs to so the responses can be regrouped afterwards.
+    let mut owners: Vec<usize> = Vec::new();
+    let mut requests: Vec<FeedRequest> = Vec::new();
+    for (idx, (feed, local_watchlist)) in pending.iter().enumerate() {
+        for request in feed_requests(feed, local_watchlist) {
+            owners.push(idx);
+            requests.push(request);
+        }
+    }
+
+    let mut responses: Vec<Vec<Result<FeedFetch>>> = pending.iter().map(|_| Vec::new()).collect();
+    for (idx, result) in owners
+        .into_iter()
+        .zip(fetch_all(conn, requests, force).await)
+    {
+        responses[idx].push(result);
+    }
+
+    summary.feeds = pending.len() as u32;
+    for ((feed, local_watchlist), results) in pending.into_iter().zip(responses) {
+        let gathered = gather_items(&feed, results);
+        if gathered.errors > 0 {
+            summary.failed += 1;
+        }
+        let (etag, last_modified) = match gathered.validators {
+            Some(validators) => validators,
+            None => continue,
+        };
+
+        let failed_before = summary.failed_downloads;
+        let one_off = local_watchlist.iter().any(|item| item.id == -1);
+        match nyaadle_logic(
+            conn,
+            gathered.items,
+            local_watchlist,
+            &feed.name,
+            check,
+            force,
+            &mut summary,
+        )
+        .await
+        {
+            Ok(()) => {
+                // Only trust the validators once the real watch-list has
+                // handled the items, otherwise a --check run, a one-off
+                // `parse -t` run or a failed download would hide them
+                // behind a 304.
+                let handled = summary.failed_downloads == failed_before;
+                if handled && !check && !one_off && feed.id != -1 && feed.kind == FeedKind::Rss {
+                    let _ = settings::update_feed_cache(
+                        conn,
+                        feed.id,
+                        etag.as_deref(),
+                        last_modified.as_deref(),
+                    );
                 }
-            },
+            }
             Err(e) => {
-                error!("Failed to fetch feed {}: {}", feed.name, e);
+                error!(
+                    event = "feed_error",
+                    feed = feed.name.as_str();
+                    "Error processing feed logic for {}: {}", feed.name, e
+                );
+                if gathered.errors == 0 {
+                    summary.failed += 1;
+                }
                 continue;
             }
-        };
+        }
+    }
 
-        let channel = match Channel::read_from(&content[..]) {
-            Ok(ch) => ch,
-            Err(e) => {
-                error!("Failed to parse RSS for feed {}: {}", feed.name, e);
-                continue;
+    summary
+}
+
+/// Placeholder in a search feed's URL that is replaced by each watch-list
+/// title.
+pub const QUERY_PLACEHOLDER: &str = "{query}";
+
+/// A single HTTP request made on behalf of a feed.
+#[derive(Clone, Debug)]
+struct FeedRequest {
+    /// Name of the feed, for logging.
+    feed: String,
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Builds the requests needed to check a feed. RSS feeds are a single
+/// request carrying the cache validators from the last fetch; Torznab and
+/// search feeds are queried once per watch-list entry.
+fn feed_requests(feed: &settings::Feed, watch_list: &[Watchlist]) -> Vec<FeedRequest> {
+    match feed.kind {
+        FeedKind::Search if !feed.url.contains(QUERY_PLACEHOLDER) => {
+            error!(
+                "Search feed {} has no {} placeholder in its URL.",
+                feed.name, QUERY_PLACEHOLDER
+            );
+            Vec::new()
+        }
+        FeedKind::Search => watch_list
+            .iter()
+            .map(|item| FeedRequest {
+                feed: feed.name.clone(),
+                url: feed
+                    .url
+                    .replace(QUERY_PLACEHOLDER, &encode_query(&item.title)),
+                etag: None,
+                last_modified: None,
+            })
+            .collect(),
+        FeedKind::Rss => vec![FeedRequest {
+            feed: feed.name.clone(),
+            url: feed.url.clone(),
+            etag: feed.etag.clone(),
+            last_modified: feed.last_modified.clone(),
+        }],
+        FeedKind::Torznab => watch_list
+            .iter()
+            .filter_map(|item| match torznab_url(feed, &item.title) {
+                Ok(url) => Some(FeedRequest {
+                    feed: feed.name.clone(),
+                    url,
+                    etag: None,
+                    last_modified: None,
+                }),
+                Err(e) => {
+                    error!("Invalid Torznab URL for feed {}: {}", feed.name, e);
+                    None
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Percent-encodes a watch-list title for use inside a URL query.
+pub fn encode_query(query: &str) -> String {
+    let mut encoded = String::new();
+    for byte in query.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
             }
-        };
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Builds a Torznab search URL for `query` from the feed's endpoint, API
+/// key and categories.
+pub fn torznab_url(feed: &settings::Feed, query: &str) -> Result<String> {
+    let mut url = reqwest::Url::parse(&feed.url)
+        .map_err(|e| Error::Config(format!("invalid URL {}: {}", feed.url, e)))?;
+    {
+        let mut pairs = url.query_pairs_mut();
+        pairs.append_pair("t", "search");
+        if let Some(key) = feed.api_key.as_deref().filter(|k| !k.is_empty()) {
+            pairs.append_pair("apikey", key);
+        }
+        if let Some(cats) = feed.categories.as_deref().filter(|c| !c.is_empty()) {
+            pairs.append_pair("cat", cats);
+        }
+        pairs.append_pair("q", query);
+    }
+    Ok(url.into())
+}
+
+/// The items merged from a feed's responses by `gather_items`.
+struct Gathered {
+    items: Vec<rss::Item>,
+    /// ETag and Last-Modified of the last response that parsed, or `None`
+    /// if nothing new came back.
+    validators: Option<(Option<String>, Option<String>)>,
+    /// Responses that couldn't be fetched or parsed. Each is logged.
+    errors: usize,
+}
+
+/// Parses every response for a feed and merges the items, dropping
+/// duplicates returned by more than one query.
+fn gather_items(feed: &settings::Feed, results: Vec<Result<FeedFetch>>) -> Gathered {
+    let mut items: Vec<rss::Item> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut validators = None;
+    let mut errors = 0;
 
-        match nyaadle_logic(conn, channel.items, local_watchlist, check, force).await {
-            Ok(count) => total_downloaded += count as u32,
+    for result in results {
+        match result {
+            Ok(FeedFetch::Modified {
+                body,
+                etag,
+                last_modified,
+            }) => match parse_items(&body) {
+                Ok(parsed) => {
+                    validators = Some((etag, last_modified));
+                    for item in parsed {
+                        if seen.insert(item_key(&item)) {
+                            items.push(item);
+                        }
+                    }
+                }
+                Err(e) => {
+                    errors += 1;
+                    error!(
+                        event = "parse_error",
+                        feed = feed.name.as_str();
+                        "Failed to parse feed {}: {}", feed.name, e
+                    );
+                }
+            },
+            Ok(FeedFetch::NotModified) => {
+                debug!("Feed {} not modified since last check.", feed.name);
+            }
             Err(e) => {
-                error!("Error processing feed logic for {}: {}", feed.name, e);
-                continue;
+                errors += 1;
+                error!(
+                    event = "fetch_error",
+                    feed = feed.name.as_str();
+                    "Failed to fetch feed {}: {}", feed.name, e
+                );
             }
         }
     }
 
-    if total_downloaded == 0 {
-        debug!("Nyaadle finished. No new items downloaded.");
-    } else {
-        info!("Nyaadle finished. Total items downloaded: {}", total_downloaded);
-        let _ = settings::write_log(
-            conn,
-            "INFO",
-            &format!("Nyaadle finished. Total items downloaded: {}", total_downloaded),
-        );
+    Gathered {
+        items,
+        validators,
+        errors,
     }
+}
 
-    Ok(())
+/// Identifies an item for de-duplication, preferring its GUID.
+pub fn item_key(item: &rss::Item) -> String {
+    item.guid()
+        .map(|g| g.value().to_string())
+        .or_else(|| item.link().map(String::from))
+        .or_else(|| item.title().map(String::from))
+        .unwrap_or_default()
+}
+
+/// Fetches a single URL and parses it into items, ignoring any cached
+/// validators. Used by backfill to walk through pages of search results.
+pub async fn fetch_items(conn: &Connection, feed: &str, url: &str) -> Result<Vec<rss::Item>> {
+    let request = FeedRequest {
+        feed: feed.to_string(),
+        url: url.to_string(),
+        etag: None,
+        last_modified: None,
+    };
+    match fetch_all(conn, vec![request], true).await.pop() {
+        Some(Ok(FeedFetch::Modified { body, .. })) => parse_items(&body),
+        Some(Ok(FeedFetch::NotModified)) => Ok(Vec::new()),
+        Some(Err(e)) => Err(e),
+        None => Err(Error::Feed(format!("no response for {}", url))),
+    }
+}
+
+/// Fetches every request concurrently, bounded by the `fetch-limit` and
+/// `fetch-timeout` settings. Results are returned in the same order as
+/// `requests` regardless of which finished first.
+async fn fetch_all(
+    conn: &Connection,
+    requests: Vec<FeedRequest>,
+    force: bool,
+) -> Vec<Result<FeedFetch>> {
+    let limit = settings::get_fetch_limit(conn) as usize;
+    let timeout = Duration::from_secs(settings::get_fetch_timeout(conn));
+
+    let client = match reqwest::Client::builder().timeout(timeout).build() {
+        Ok(client) => client,
+        Err(e) => {
+            let msg = e.to_string();
+            return requests
+                .iter()
+                .map(|_| {
+                    Err(Error::Feed(format!(
+                        "couldn't set up the HTTP client: {}",
+                        msg
+                    )))
+                })
+                .collect();
+        }
+    };
+    let permits = Arc::new(Semaphore::new(limit));
+    let mut tasks = JoinSet::new();
+
+    for (idx, request) in requests.iter().cloned().enumerate() {
+        let client = client.clone();
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let started = Instant::now();
+            let result = fetch_feed(&client, &request, force).await;
+            if result.is_ok() {
+                debug!(
+                    event = "fetch",
+                    feed = request.feed.as_str(),
+                    url = request.url.as_str(),
+                    duration_ms = started.elapsed().as_millis() as u64;
+                    "Fetched {}", request.url
+                );
+            }
+            (idx, result)
+        });
+    }
+
+    let mut results: Vec<Option<Result<FeedFetch>>> = requests.iter().map(|_| None).collect();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((idx, result)) => results[idx] = Some(result),
+            Err(e) => error!("Feed fetch task failed: {}", e),
+        }
+    }
+
+    results
+        .into_iter()
+        .map(|r| r.unwrap_or_else(|| Err(Error::Feed(String::from("fetch task did not complete")))))
+        .collect()
+}
+
+/// Parses a feed body as RSS, falling back to Atom when it isn't an RSS
+/// document. Atom entries are converted into RSS items so the rest of the
+/// matching logic only has to deal with one representation.
+///
+/// RSS items without a `<link>` (as returned by most Torznab indexers) use
+/// their enclosure URL instead.
+pub fn parse_items(content: &[u8]) -> Result<Vec<rss::Item>> {
+    let rss_err = match Channel::read_from(content) {
+        Ok(channel) => {
+            let mut items = channel.items;
+            for item in &mut items {
+                if item.link().is_none() {
+                    let url = item.enclosure().map(|e| e.url().to_string());
+                    item.set_link(url);
+                }
+            }
+            return Ok(items);
+        }
+        Err(e) => e,
+    };
+    match atom_syndication::Feed::read_from(content) {
+        Ok(feed) => Ok(feed.entries().iter().map(atom_to_item).collect()),
+        Err(atom_err) => Err(Error::Feed(format!(
+            "not a valid RSS ({}) or Atom ({}) feed",
+            rss_err, atom_err
+        ))),
+    }
+}
+
+/// Maps an Atom entry onto an RSS item. The download link is taken from an
+/// `enclosure` link if there is one, otherwise from the first link.
+fn atom_to_item(entry: &atom_syndication::Entry) -> rss::Item {
+    let link = entry
+        .links()
+        .iter()
+        .find(|l| l.rel() == "enclosure")
+        .or_else(|| entry.links().first())
+        .map(|l| l.href().to_string());
+
+    let mut guid = rss::Guid::default();
+    guid.set_value(entry.id());
+
+    let mut item = rss::Item::default();
+    item.set_title(entry.title().value.clone());
+    item.set_link(link);
+    item.set_guid(guid);
+    item.set_pub_date(
+        entry
+            .published()
+            .unwrap_or_else(|| entry.updated())
+            .to_rfc2822(),
+    );
+    item
+}
+
+/// Result of a feed request.
+enum FeedFetch {
+    /// The server returned a fresh copy along with its cache validators.
+    Modified {
+        body: Vec<u8>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+    /// The server answered 304; nothing changed since the last fetch.
+    NotModified,
+}
+
+/// Requests a feed, sending `If-None-Match` / `If-Modified-Since` from the
+/// previous response so unchanged feeds cost a 304 instead of a full
+/// download. `force` skips the validators and always fetches in full.
+async fn fetch_feed(
+    client: &reqwest::Client,
+    feed: &FeedRequest,
+    force: bool,
+) -> Result<FeedFetch> {
+    let mut request = client.get(&feed.url);
+    if !force {
+        if let Some(etag) = &feed.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &feed.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(FeedFetch::NotModified);
+    }
+    let response = response.error_for_status()?;
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+    };
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+    let body = response.bytes().await?.to_vec();
+
+    Ok(FeedFetch::Modified {
+        body,
+        etag,
+        last_modified,
+    })
+}
+
+/// Returns true if an item title matches a watch-list entry. The entry's
+/// title must appear in the item title and, unless the option is
+/// `non-vid`, so must the option.
+pub fn is_match(title: &str, entry: &Watchlist) -> bool {
+    title.contains(&entry.title) && option_matches(title, &entry.option)
+}
+
+/// Returns true if an item title meets a watch-list entry's download
+/// option on its own.
+pub fn option_matches(title: &str, option: &str) -> bool {
+    option == NON_VID || title.contains(option)
 }
 
-/// Iterates the watchlist against RSS feed items and downloads matches.
+/// Download option that matches on title alone.
+pub const NON_VID: &str = "non-vid";
+
+/// Iterates the watchlist against RSS feed items and downloads matches,
+/// adding them to `summary`. With `check`, nothing is downloaded and the
+/// plan for each entry is added to `summary.found` instead. An item that
+/// fails to download is logged and counted without stopping the rest.
 ///
 /// Download options:
 /// - A resolution string (`1080`, `720`, `480`) for video items.
@@ -339,63 +966,342 @@ pub async fn nyaadle_logic(
     conn: &Connection,
     items: Vec<rss::Item>,
     watch_list: Vec<Watchlist>,
+    feed: &str,
     check: bool,
     force: bool,
-) -> Result<i32> {
-    let non_opt = "non-vid";
-    let mut num_dl: i32 = 0;
-    println!("Checking watch-list...\n");
+    summary: &mut Summary,
+) -> Result<()> {
+    status!("Checking watch-list...\n");
 
     for anime in &watch_list {
         if anime.option.is_empty() {
             warn!("Download option not found for \"{}\".", anime.title);
-            let _ = settings::write_log(
-                conn,
-                "WARN",
-                &format!("Download option not found for \"{}\".", anime.title),
-            );
-            println!("Please set a download option using 'nyaadle wle --edit'");
+            status!("Please set a download option using 'nyaadle wle --edit'");
+            if check {
+                plan_entry(conn, &items, anime, feed, force, &mut summary.found)?;
+            }
             continue;
         }
 
-        println!("Checking for {}", &anime.title);
+        status!("Checking for {}", &anime.title);
+        if check {
+            plan_entry(conn, &items, anime, feed, force, &mut summary.found)?;
+            continue;
+        }
 
         // Collect all matching items before acting on any of them
         let matches: Vec<&rss::Item> = items
             .iter()
-            .filter(|item| {
-                let title = match item.title() {
-                    Some(t) => t,
-                    None => return false,
-                };
-                if !title.contains(&anime.title) {
-                    return false;
-                }
-                if anime.option == non_opt {
-                    true
-                } else {
-                    title.contains(&anime.option)
-                }
-            })
+            .filter(|item| item.title().is_some_and(|title| is_match(title, anime)))
             .collect();
 
         if matches.is_empty() {
-            println!("No matches found for {}\n", &anime.title);
+            status!("No matches found for {}\n", &anime.title);
             continue;
         }
 
         for item in matches {
             let title = item.title().unwrap_or("unknown");
-            if check {
-                println!("Found {}\n", title);
-            } else {
-                match download_logic(conn, item, &anime.title, force, anime.feed_id).await? {
-                    1 => num_dl += 1,
-                    _ => {}
+            match download_logic(conn, item, anime, feed, force).await {
+                Ok(1) => summary.downloaded += 1,
+                Ok(_) => {}
+                Err(e) => {
+                    summary.failed_downloads += 1;
+                    download_failed(Some(feed), title, &e);
                 }
             }
         }
     }
 
-    Ok(num_dl)
+    Ok(())
+}
+
+/// Adds what a run would do with each item whose title contains `entry`'s
+/// title to `plan`, checking the same things `download_logic` and
+/// `downloader` do but without downloading or updating the tracking table.
+fn plan_entry(
+    conn: &Connection,
+    items: &[rss::Item],
+    entry: &Watchlist,
+    feed: &str,
+    force: bool,
+    plan: &mut Vec<Match>,
+) -> Result<()> {
+    let dl_dir = settings::get_settings(conn, "dl-dir")?;
+    let ar_dir = settings::get_settings(conn, "ar-dir")?;
+    let tracked = settings::get_tracking(conn, &entry.title, entry.feed_id)?;
+
+    for item in items {
+        let title = match item.title() {
+            Some(title) if title.contains(&entry.title) => title,
+            _ => continue,
+        };
+        let url = item.link().filter(|url| !url.is_empty());
+        let file = url
+            .filter(|url| !url.contains("magnet:"))
+            .map(filename_from_url);
+
+        let skip = if entry.option.is_empty() {
+            Some(Skip::NoOption)
+        } else if !is_match(title, entry) {
+            Some(Skip::OptionMismatch)
+        } else if url.is_none() {
+            Some(Skip::NoLink)
+        } else if !force && tracked == title {
+            Some(Skip::Tracked)
+        } else if !force && file.is_some_and(|file| Path::new(&ar_dir).join(file).exists()) {
+            Some(Skip::Archived)
+        } else {
+            None
+        };
+
+        plan.push(Match {
+            feed: feed.to_string(),
+            watch_id: entry.id,
+            item: entry.title.clone(),
+            option: entry.option.clone(),
+            title: title.to_string(),
+            url: url.map(String::from),
+            skip,
+            destination: file.map(|file| format!("{}/{}", dl_dir, file)),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const TORZNAB_RESPONSE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:torznab="http://torznab.com/schemas/2015/feed">
+  <channel>
+    <title>Jackett</title>
+    <link>http://127.0.0.1/</link>
+    <description>stub</description>
+    <item>
+      <title>[Sub] Frieren - 07 [1080p].mkv</title>
+      <guid>frieren-07</guid>
+      <enclosure url="http://127.0.0.1/dl/7.torrent" length="100" type="application/x-bittorrent"/>
+      <torznab:attr name="seeders" value="10"/>
+    </item>
+  </channel>
+</rss>"#;
+
+    /// Serves `body` to a single request and hands back the request line.
+    async fn stub_server(body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        stub_response("200 OK", body).await
+    }
+
+    /// Answers a single request with `status` and `body`, returning the
+    /// server's URL and the request line it received.
+    async fn stub_response(
+        status: &'static str,
+        body: &'static str,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/rss+xml\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            let request = String::from_utf8_lossy(&request).to_string();
+            request.lines().next().unwrap_or_default().to_string()
+        });
+        (url, handle)
+    }
+
+    fn watch_entry(id: i32, title: &str) -> Watchlist {
+        Watchlist::new().build(id, String::from(title), String::from("1080"), -1)
+    }
+
+    #[test]
+    fn search_feed_expands_per_watchlist_entry() {
+        let mut feed =
+            settings::Feed::temporary(String::from("https://nyaa.si/?page=rss&q={query}&c=1_2"));
+        feed.kind = FeedKind::Search;
+        let watch_list = vec![
+            watch_entry(1, "Sousou no Frieren"),
+            watch_entry(2, "Dandadan"),
+        ];
+
+        let urls: Vec<String> = feed_requests(&feed, &watch_list)
+            .into_iter()
+            .map(|r| r.url)
+            .collect();
+
+        assert_eq!(
+            urls,
+            vec![
+                "https://nyaa.si/?page=rss&q=Sousou%20no%20Frieren&c=1_2",
+                "https://nyaa.si/?page=rss&q=Dandadan&c=1_2",
+            ]
+        );
+    }
+
+    #[test]
+    fn results_are_deduplicated_across_queries() {
+        let feed = settings::Feed::temporary(String::new());
+        let response = || {
+            Ok(FeedFetch::Modified {
+                body: TORZNAB_RESPONSE.as_bytes().to_vec(),
+                etag: None,
+                last_modified: None,
+            })
+        };
+
+        let gathered = gather_items(&feed, vec![response(), response()]);
+
+        assert_eq!(gathered.items.len(), 1);
+        assert_eq!(gathered.errors, 0);
+    }
+
+    #[tokio::test]
+    async fn torznab_feed_searches_watchlist_title() {
+        let conn = Connection::open_in_memory().unwrap();
+        let (url, request) = stub_server(TORZNAB_RESPONSE).await;
+
+        let mut feed = settings::Feed::temporary(url);
+        feed.kind = FeedKind::Torznab;
+        feed.api_key = Some(String::from("secret"));
+        feed.categories = Some(String::from("5070"));
+        let watch_list = vec![watch_entry(1, "Frieren")];
+
+        let requests = feed_requests(&feed, &watch_list);
+        let results = fetch_all(&conn, requests, false).await;
+        let gathered = gather_items(&feed, results);
+        assert!(gathered.validators.is_some(), "stub returned no items");
+        let items = gathered.items;
+
+        assert_eq!(
+            request.await.unwrap(),
+            "GET /api?t=search&apikey=secret&cat=5070&q=Frieren HTTP/1.1"
+        );
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].title(), Some("[Sub] Frieren - 07 [1080p].mkv"));
+        assert_eq!(items[0].link(), Some("http://127.0.0.1/dl/7.torrent"));
+    }
+
+    #[tokio::test]
+    async fn one_off_entries_leave_the_feed_cache_alone() {
+        let conn = Connection::open_in_memory().unwrap();
+        settings::db_create(&conn).unwrap();
+        let (url, _request) = stub_server(TORZNAB_RESPONSE).await;
+        settings::db_write_feed(&conn, "Local", &url, true).unwrap();
+        let feeds = settings::read_feeds(&conn).unwrap();
+
+        let summary = parse_feeds(&conn, feeds, &[watch_entry(-1, "Dandadan")], false, false).await;
+        assert_eq!(summary.failed, 0);
+
+        let feed = &settings::read_feeds(&conn).unwrap()[0];
+        assert_eq!(feed.etag, None);
+        assert_eq!(feed.last_checked, None);
+    }
+
+    #[tokio::test]
+    async fn error_pages_are_not_saved_as_downloads() {
+        let conn = Connection::open_in_memory().unwrap();
+        settings::db_create(&conn).unwrap();
+        let dir = std::env::temp_dir().join(format!("nyaadle-404-{}", std::process::id()));
+        let dir_str = dir.to_str().unwrap();
+        settings::db_write_dir(&conn, "dl-dir", dir_str).unwrap();
+        settings::db_write_dir(&conn, "ar-dir", &format!("{}/archive", dir_str)).unwrap();
+        let (url, _request) = stub_response("404 Not Found", "no such torrent").await;
+
+        let result = downloader(&conn, &format!("{}/7.torrent", url), "7", false).await;
+        let saved = dir.join("7.torrent").exists();
+
+        // A failed `nyaadle dl` link isn't tracked, so it can be retried.
+        let (url, _request) = stub_response("404 Not Found", "no such torrent").await;
+        let link = format!("{}/8.torrent", url);
+        let summary = arg_dl(&conn, vec![link.clone()]).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(result, Err(Error::Network(_))));
+        assert!(!saved);
+        assert_eq!(summary.failed_downloads, 1);
+        assert_eq!(settings::get_tracking(&conn, &link, 0).unwrap(), "");
+    }
+
+    fn item(title: &str, link: &str) -> rss::Item {
+        let mut item = rss::Item::default();
+        item.set_title(String::from(title));
+        item.set_link(String::from(link));
+        item
+    }
+
+    #[test]
+    fn check_plan_gives_a_reason_for_each_skip() {
+        let conn = Connection::open_in_memory().unwrap();
+        settings::db_create(&conn).unwrap();
+        let dir = std::env::temp_dir().join(format!("nyaadle-plan-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("6.torrent"), b"").unwrap();
+        let dir = dir.to_str().unwrap();
+        settings::db_write_dir(&conn, "dl-dir", "/srv/downloads").unwrap();
+        settings::db_write_dir(&conn, "ar-dir", dir).unwrap();
+        settings::update_tracking(&conn, "Frieren", "[Sub] Frieren - 05 [1080p].mkv", -1).unwrap();
+
+        let items = vec![
+            item(
+                "[Sub] Frieren - 07 [1080p].mkv",
+                "http://127.0.0.1/dl/7.torrent",
+            ),
+            item(
+                "[Sub] Frieren - 07 [720p].mkv",
+                "http://127.0.0.1/dl/7-720.torrent",
+            ),
+            item(
+                "[Sub] Frieren - 06 [1080p].mkv",
+                "http://127.0.0.1/dl/6.torrent",
+            ),
+            item(
+                "[Sub] Frieren - 05 [1080p].mkv",
+                "http://127.0.0.1/dl/5.torrent",
+            ),
+            item(
+                "[Sub] Dandadan - 07 [1080p].mkv",
+                "http://127.0.0.1/dl/d7.torrent",
+            ),
+        ];
+        let mut plan = Vec::new();
+        plan_entry(
+            &conn,
+            &items,
+            &watch_entry(1, "Frieren"),
+            "Nyaa",
+            false,
+            &mut plan,
+        )
+        .unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+
+        let skips: Vec<Option<Skip>> = plan.iter().map(|m| m.skip).collect();
+        assert_eq!(
+            skips,
+            vec![
+                None,
+                Some(Skip::OptionMismatch),
+                Some(Skip::Archived),
+                Some(Skip::Tracked)
+            ]
+        );
+        assert_eq!(
+            plan[0].destination.as_deref(),
+            Some("/srv/downloads/7.torrent")
+        );
+    }
 }
@@ -1,10 +1,111 @@
+use crate::error::{self, Error};
+use crate::migrations;
+use crate::status;
+use log::{Level, LevelFilter};
 use rusqlite::{named_params, params, Connection};
-use std::fs::File;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 use time::format_description;
-use time::OffsetDateTime;
+use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+/// The `db-ver` written by the newest entry in `migrations::MIGRATIONS`.
+pub const CURRENT_DB_VERSION: &str = "3.7";
+
+/// Minutes between feed checks when running as a daemon.
+pub const DEFAULT_POLL_INTERVAL: u64 = 30;
+
+/// Number of feeds fetched at the same time.
+pub const DEFAULT_FETCH_LIMIT: u64 = 4;
+
+/// Seconds to wait for a single feed before giving up on it.
+pub const DEFAULT_FETCH_TIMEOUT: u64 = 30;
+
+/// Number of automatic pre-migration backups kept in the backups folder.
+pub const DEFAULT_BACKUP_KEEP: u64 = 5;
+
+/// Days of entries kept in the logs table. 0 keeps them forever.
+pub const DEFAULT_LOG_KEEP_DAYS: u64 = 30;
+
+/// Most entries kept in the logs table. 0 means no limit.
+pub const DEFAULT_LOG_KEEP_ROWS: u64 = 5000;
+
+/// Size in MB the log file may reach before it is rotated. 0 never rotates.
+pub const DEFAULT_LOG_MAX_SIZE: u64 = 5;
+
+/// Level below which messages are dropped from the log file and table.
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+
+/// Format of the log file: plain text or one JSON object per line.
+pub const DEFAULT_LOG_FORMAT: &str = "text";
+
+/// How long a connection waits for another process to release the
+/// database before giving up.
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Environment variable that points nyaadle at a different database.
+pub const DB_ENV: &str = "NYAADLE_DB";
+
+/// Database path picked by `select_db` for the command line and the TUI.
+/// A `Store` carries its own path on its connection instead.
+static DB_PATH: OnceLock<String> = OnceLock::new();
+
+/// Environment variable that points nyaadle at a different config file.
+pub const CONFIG_ENV: &str = "NYAADLE_CONFIG";
+
+/// Settings that can be layered from a config file, the environment or
+/// the command line. Anything else is only ever read from the database.
+pub const LAYERED_KEYS: &[&str] = &[
+    "dl-dir",
+    "ar-dir",
+    "log",
+    "webhk_url",
+    "poll-interval",
+    "fetch-limit",
+    "fetch-timeout",
+    "backup-keep",
+    "log-level",
+    "log-format",
+    "log-keep-days",
+    "log-keep-rows",
+    "log-max-size",
+];
+
+/// Values from the config file and command line loaded by `load_layers`,
+/// kept per database under the path its connections report.
+static LAYERS: Mutex<BTreeMap<String, Arc<Layers>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Default)]
+struct Layers {
+    file_path: String,
+    file: BTreeMap<String, String>,
+    cli: BTreeMap<String, String>,
+}
+
+/// Where the effective value of a setting came from, lowest priority first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    Database,
+    ConfigFile(String),
+    Environment(String),
+    CommandLine,
+}
 
-pub const CURRENT_DB_VERSION: &str = "3.0";
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::Database => write!(f, "database"),
+            Origin::ConfigFile(path) => write!(f, "config file {}", path),
+            Origin::Environment(var) => write!(f, "environment {}", var),
+            Origin::CommandLine => write!(f, "command line"),
+        }
+    }
+}
 
 /// Settings Struct
 struct Settings {
@@ -19,6 +120,24 @@ struct Settings {
     ver_val: String,
     whkurl_key: String,
     whkurl_val: String,
+    poll_key: String,
+    poll_val: String,
+    limit_key: String,
+    limit_val: String,
+    timeout_key: String,
+    timeout_val: String,
+    keep_key: String,
+    keep_val: String,
+    level_key: String,
+    level_val: String,
+    format_key: String,
+    format_val: String,
+    days_key: String,
+    days_val: String,
+    rows_key: String,
+    rows_val: String,
+    size_key: String,
+    size_val: String,
 }
 
 /// Public Watchlist Struct
@@ -37,6 +156,41 @@ pub struct Log {
     pub timestamp: String,
     pub level: String,
     pub message: String,
+    /// The module that logged the entry, e.g. `nyaadle::parse`.
+    pub target: String,
+    /// Structured fields attached to the entry, as a JSON object.
+    pub fields: Option<String>,
+}
+
+/// A row of the download history.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Download {
+    pub id: i64,
+    /// Unix time of the download.
+    pub downloaded_at: i64,
+    /// Name of the feed the item came from. `None` for direct downloads.
+    pub feed: Option<String>,
+    /// ID and title of the watch-list entry that matched, as they were at
+    /// the time.
+    pub watch_id: Option<i32>,
+    pub item: Option<String>,
+    pub title: String,
+    pub url: Option<String>,
+}
+
+/// Narrows down `read_downloads`. Empty filters match everything.
+#[derive(Clone, Debug, Default)]
+pub struct HistoryFilter {
+    /// Only downloads at or after this unix time.
+    pub since: Option<i64>,
+    /// Feed name, ignoring case.
+    pub feed: Option<String>,
+    /// A watch-list ID, or part of a watch-list title.
+    pub item: Option<String>,
+    /// Part of the downloaded title, ignoring case.
+    pub grep: Option<String>,
+    /// Most rows returned, newest first.
+    pub limit: Option<usize>,
 }
 
 #[derive(Clone, Debug)]
@@ -45,6 +199,78 @@ pub struct Feed {
     pub name: String,
     pub url: String,
     pub is_default: bool,
+    pub enabled: bool,
+    /// Minutes to wait between checks. 0 checks on every run.
+    pub interval_minutes: i64,
+    /// Unix timestamp of the last time this feed was fetched.
+    pub last_checked: Option<i64>,
+    /// `ETag` header from the last successful fetch.
+    pub etag: Option<String>,
+    /// `Last-Modified` header from the last successful fetch.
+    pub last_modified: Option<String>,
+    pub kind: FeedKind,
+    /// Torznab API key.
+    pub api_key: Option<String>,
+    /// Comma-separated Torznab category IDs.
+    pub categories: Option<String>,
+}
+
+/// Where a feed's items come from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FeedKind {
+    /// A static RSS or Atom URL.
+    Rss,
+    /// A Torznab API endpoint (e.g. Jackett or Prowlarr), searched once per
+    /// watch-list entry.
+    Torznab,
+    /// A URL template containing `{query}`, expanded once per watch-list
+    /// entry.
+    Search,
+}
+
+impl FeedKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeedKind::Rss => "rss",
+            FeedKind::Torznab => "torznab",
+            FeedKind::Search => "search",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<FeedKind> {
+        match kind {
+            "rss" => Some(FeedKind::Rss),
+            "torznab" => Some(FeedKind::Torznab),
+            "search" => Some(FeedKind::Search),
+            _ => None,
+        }
+    }
+}
+
+/// How the log file is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// simplelog's human-readable lines.
+    Text,
+    /// One JSON object per line, for log shippers such as Vector or Loki.
+    Json,
+}
+
+impl LogFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        }
+    }
+
+    pub fn parse(format: &str) -> Option<LogFormat> {
+        match format {
+            "text" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
 }
 
 impl Settings {
@@ -60,11 +286,7 @@ impl Settings {
         ar_dir.push("archive");
         let ar_dir = String::from(ar_dir.to_str().unwrap());
 
-        let mut log_path = dirs::config_dir().unwrap();
-        log_path.push("nyaadle");
-        log_path.push("nyaadle");
-        log_path.set_extension("log");
-        let log_path = String::from(log_path.to_str().unwrap());
+        let log_path = default_log_path(settings_dir());
 
         Settings {
             dl_key: String::from("dl-dir"),
@@ -78,11 +300,63 @@ impl Settings {
             ver_val: String::from("2.0"),
             whkurl_key: String::from("webhk_url"),
             whkurl_val: String::from(""),
+            poll_key: String::from("poll-interval"),
+            poll_val: DEFAULT_POLL_INTERVAL.to_string(),
+            limit_key: String::from("fetch-limit"),
+            limit_val: DEFAULT_FETCH_LIMIT.to_string(),
+            timeout_key: String::from("fetch-timeout"),
+            timeout_val: DEFAULT_FETCH_TIMEOUT.to_string(),
+            keep_key: String::from("backup-keep"),
+            keep_val: DEFAULT_BACKUP_KEEP.to_string(),
+            level_key: String::from("log-level"),
+            level_val: String::from(DEFAULT_LOG_LEVEL),
+            format_key: String::from("log-format"),
+            format_val: String::from(DEFAULT_LOG_FORMAT),
+            days_key: String::from("log-keep-days"),
+            days_val: DEFAULT_LOG_KEEP_DAYS.to_string(),
+            rows_key: String::from("log-keep-rows"),
+            rows_val: DEFAULT_LOG_KEEP_ROWS.to_string(),
+            size_key: String::from("log-max-size"),
+            size_val: DEFAULT_LOG_MAX_SIZE.to_string(),
+        }
+    }
+}
+
+impl Feed {
+    /// Builds a feed that isn't stored in the database, used when a URL
+    /// is passed on the command line.
+    pub fn temporary(url: String) -> Feed {
+        Feed {
+            id: -1,
+            name: String::from("Temporary Feed"),
+            url,
+            is_default: false,
+            enabled: true,
+            interval_minutes: 0,
+            last_checked: None,
+            etag: None,
+            last_modified: None,
+            kind: FeedKind::Rss,
+            api_key: None,
+            categories: None,
+        }
+    }
+
+    /// Returns true if the feed is enabled and its interval has elapsed
+    /// since it was last checked.
+    pub fn is_due(&self, now: i64) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        match self.last_checked {
+            Some(last) if self.interval_minutes > 0 => now - last >= self.interval_minutes * 60,
+            _ => true,
         }
     }
 }
 
 impl Watchlist {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Watchlist {
         Watchlist {
             id: 0,
@@ -110,8 +384,16 @@ impl Watchlist {
     }
 }
 
-/// Returns the path to the nyaadle database.
+/// Returns the path to the nyaadle database: the one picked by
+/// `select_db`, or `~/.config/nyaadle/nyaadle.db`.
 pub fn settings_dir() -> String {
+    if let Some(path) = DB_PATH.get() {
+        return path.clone();
+    }
+    default_db()
+}
+
+fn default_db() -> String {
     let mut set_dir = dirs::config_dir().unwrap();
     set_dir.push("nyaadle");
     set_dir.push("nyaadle");
@@ -119,29 +401,270 @@ pub fn settings_dir() -> String {
     String::from(set_dir.to_str().unwrap())
 }
 
+/// Works out which database to use. `--db` wins over `--profile`, which
+/// wins over the `NYAADLE_DB` environment variable, which wins over
+/// `~/.config/nyaadle/nyaadle.db`.
+pub fn find_db(db: Option<String>, profile: Option<&str>) -> Result<String, String> {
+    match (db, profile) {
+        (Some(path), _) => Ok(path),
+        (None, Some(name)) => profile_db(name),
+        (None, None) => Ok(std::env::var(DB_ENV)
+            .ok()
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(default_db)),
+    }
+}
+
+/// Picks the database for the command line, see `find_db`. Must be called
+/// before anything opens the database.
+pub fn select_db(db: Option<String>, profile: Option<&str>) -> Result<(), String> {
+    let _ = DB_PATH.set(find_db(db, profile)?);
+    Ok(())
+}
+
+/// Returns the file behind `conn`, or `None` for an in-memory database.
+pub fn db_file(conn: &Connection) -> Option<PathBuf> {
+    conn.path()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Returns the database path for a named profile,
+/// `~/.config/nyaadle/profiles/<name>/nyaadle.db`.
+fn profile_db(name: &str) -> Result<String, String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!(
+            "Invalid profile name \"{}\". Use letters, numbers, '-' and '_'.",
+            name
+        ));
+    }
+    let mut path = dirs::config_dir().unwrap();
+    path.push("nyaadle");
+    path.push("profiles");
+    path.push(name);
+    path.push("nyaadle.db");
+    Ok(String::from(path.to_str().unwrap()))
+}
+
+/// Loads the config file and `--setting` overrides that sit on top of the
+/// database behind `conn`. The file is `--config`, then `NYAADLE_CONFIG`,
+/// then the database path with a `.toml` extension if it exists; only its
+/// `[settings]` table is used. Settings read through any connection to the
+/// same database see them.
+pub fn load_layers(
+    conn: &Connection,
+    config: Option<String>,
+    overrides: &[String],
+) -> Result<(), String> {
+    let explicit = config.or_else(|| std::env::var(CONFIG_ENV).ok().filter(|p| !p.is_empty()));
+    let file_path = explicit.clone().unwrap_or_else(|| {
+        let db = db_file(conn).unwrap_or_else(|| PathBuf::from(settings_dir()));
+        db.with_extension("toml").to_string_lossy().into_owned()
+    });
+
+    let mut file = BTreeMap::new();
+    if explicit.is_some() || Path::new(&file_path).exists() {
+        let text = std::fs::read_to_string(&file_path)
+            .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
+        let parsed: crate::config::Config =
+            toml::from_str(&text).map_err(|e| format!("Failed to parse {}: {}", file_path, e))?;
+        for (key, value) in parsed.settings {
+            if LAYERED_KEYS.contains(&key.as_str()) {
+                file.insert(key, value);
+            } else {
+                status!("Ignoring unknown setting \"{}\" in {}.", key, file_path);
+            }
+        }
+    }
+
+    let mut cli = BTreeMap::new();
+    for pair in overrides {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("Expected KEY=VALUE, got \"{}\".", pair))?;
+        if !LAYERED_KEYS.contains(&key) {
+            return Err(format!(
+                "Unknown setting \"{}\". Expected one of: {}.",
+                key,
+                LAYERED_KEYS.join(", ")
+            ));
+        }
+        cli.insert(key.to_string(), value.to_string());
+    }
+
+    let layers = Layers {
+        file_path,
+        file,
+        cli,
+    };
+    if let Ok(mut loaded) = LAYERS.lock() {
+        loaded.insert(
+            conn.path().unwrap_or_default().to_string(),
+            Arc::new(layers),
+        );
+    }
+    Ok(())
+}
+
+/// Returns the layers loaded for the database behind `conn`, if any.
+fn layers(conn: &Connection) -> Option<Arc<Layers>> {
+    let loaded = LAYERS.lock().ok()?;
+    loaded.get(conn.path().unwrap_or_default()).cloned()
+}
+
+/// Returns the environment variable that overrides `key`, e.g.
+/// `NYAADLE_DL_DIR` for `dl-dir`.
+pub fn env_var(key: &str) -> String {
+    format!("NYAADLE_{}", key.to_uppercase().replace('-', "_"))
+}
+
+/// Resolves a setting through every layer: the command line, then
+/// `NYAADLE_*` environment variables, then the config file, then the
+/// database, then the built-in default.
+pub fn resolve_setting(conn: &Connection, key: &str) -> rusqlite::Result<(String, Origin)> {
+    if LAYERED_KEYS.contains(&key) {
+        let layers = layers(conn);
+        if let Some(value) = layers.as_ref().and_then(|l| l.cli.get(key)) {
+            return Ok((value.clone(), Origin::CommandLine));
+        }
+        let var = env_var(key);
+        if let Ok(value) = std::env::var(&var) {
+            return Ok((value, Origin::Environment(var)));
+        }
+        if let Some(layers) = layers {
+            if let Some(value) = layers.file.get(key) {
+                return Ok((value.clone(), Origin::ConfigFile(layers.file_path.clone())));
+            }
+        }
+    }
+
+    let mut stmt = conn.prepare("SELECT path FROM directories WHERE option = :name")?;
+    let mut rows = stmt.query(named_params! { ":name": key })?;
+    if let Some(row) = rows.next()? {
+        return Ok((row.get(0)?, Origin::Database));
+    }
+    Ok((
+        default_value(conn, key).unwrap_or_default(),
+        Origin::Default,
+    ))
+}
+
+/// Returns the built-in default for a layered setting.
+fn default_value(conn: &Connection, key: &str) -> Option<String> {
+    let default = Settings::default();
+    match key {
+        "dl-dir" => Some(default.dl_val),
+        "ar-dir" => Some(default.ar_val),
+        "log" => Some(db_file(conn).map_or(default.log_val, default_log_path)),
+        "webhk_url" => Some(default.whkurl_val),
+        "poll-interval" => Some(default.poll_val),
+        "fetch-limit" => Some(default.limit_val),
+        "fetch-timeout" => Some(default.timeout_val),
+        "backup-keep" => Some(default.keep_val),
+        "log-level" => Some(default.level_val),
+        "log-format" => Some(default.format_val),
+        "log-keep-days" => Some(default.days_val),
+        "log-keep-rows" => Some(default.rows_val),
+        "log-max-size" => Some(default.size_val),
+        _ => None,
+    }
+}
+
+/// Prints every layered setting with its effective value and origin.
+pub fn print_origins(conn: &Connection) {
+    println!("Setting | Value | Origin");
+    for key in LAYERED_KEYS {
+        let (value, origin) =
+            resolve_setting(conn, key).unwrap_or((String::new(), Origin::Default));
+        println!("{} | {} | {}", key, value, origin);
+    }
+}
+
+/// Returns the log file used when none is set: the database path `db`
+/// with a `.log` extension, so each profile logs next to its own database.
+pub fn default_log_path(db: impl AsRef<Path>) -> String {
+    let path = db.as_ref().with_extension("log");
+    String::from(path.to_str().unwrap())
+}
+
 /// Opens a connection to the nyaadle database.
 /// Used by main to create the shared connection, and by tui.rs for
 /// its interactive callbacks which cannot hold a borrowed reference.
+///
+/// The database is put in WAL mode so a TUI session, the daemon and a
+/// cron run can read while another one writes, and each connection waits
+/// up to `BUSY_TIMEOUT` for a lock instead of failing straight away.
 pub fn open_conn() -> rusqlite::Result<Connection> {
-    let conn = Connection::open(settings_dir())?;
+    open_conn_at(settings_dir())
+}
+
+/// Opens the database at `path` the same way as `open_conn`.
+pub fn open_conn_at(path: impl AsRef<Path>) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
     conn.execute("PRAGMA foreign_keys = ON;", [])?;
     Ok(conn)
 }
 
+/// Held while a run checks feeds and downloads so overlapping runs can't
+/// grab the same item twice. The lock is released when this is dropped,
+/// or by the OS if nyaadle dies.
+pub struct RunLock {
+    _file: Option<File>,
+}
+
+/// Returns the run lock file for the database behind `conn`: its path with
+/// a `.lock` extension. An in-memory database has no file, and so no lock.
+pub fn lock_path(conn: &Connection) -> Option<PathBuf> {
+    db_file(conn).map(|path| path.with_extension("lock"))
+}
+
+/// Takes the run lock for the database behind `conn`, or returns `None` if
+/// another run is holding it. No other process can reach an in-memory
+/// database, so its lock is always free.
+pub fn try_run_lock(conn: &Connection) -> std::io::Result<Option<RunLock>> {
+    let Some(path) = lock_path(conn) else {
+        return Ok(Some(RunLock { _file: None }));
+    };
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(RunLock { _file: Some(file) })),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
+
 /// Creates the database tables if they don't already exist.
-fn db_create(conn: &Connection) -> rusqlite::Result<()> {
+pub fn db_create(conn: &Connection) -> rusqlite::Result<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS directories (
             option TEXT PRIMARY KEY,
-            path   TEXT NOT NULL UNIQUE)",
+            path   TEXT NOT NULL)",
         [],
     )?;
     conn.execute(
         "CREATE TABLE IF NOT EXISTS feeds (
-            id         INTEGER PRIMARY KEY,
-            name       TEXT NOT NULL UNIQUE,
-            url        TEXT NOT NULL,
-            is_default INTEGER NOT NULL DEFAULT 0)",
+            id               INTEGER PRIMARY KEY,
+            name             TEXT NOT NULL UNIQUE,
+            url              TEXT NOT NULL,
+            is_default       INTEGER NOT NULL DEFAULT 0,
+            enabled          INTEGER NOT NULL DEFAULT 1,
+            interval_minutes INTEGER NOT NULL DEFAULT 0,
+            last_checked     INTEGER,
+            etag             TEXT,
+            last_modified    TEXT,
+            kind             TEXT NOT NULL DEFAULT 'rss',
+            api_key          TEXT,
+            categories       TEXT)",
         [],
     )?;
     conn.execute(
@@ -150,7 +673,7 @@ fn db_create(conn: &Connection) -> rusqlite::Result<()> {
             name    TEXT NOT NULL,
             option  TEXT NOT NULL,
             feed_id INTEGER NOT NULL REFERENCES feeds(id),
-            UNIQUE(name, feed_id))", 
+            UNIQUE(name, feed_id))",
         [],
     )?;
     conn.execute(
@@ -165,40 +688,63 @@ fn db_create(conn: &Connection) -> rusqlite::Result<()> {
     )?;
     conn.execute(
         "CREATE TABLE IF NOT EXISTS logs (
-            id        INTEGER PRIMARY KEY,
-            timestamp TEXT NOT NULL,
-            level     TEXT NOT NULL,
-            message   TEXT NOT NULL)",
+            id         INTEGER PRIMARY KEY,
+            timestamp  TEXT NOT NULL,
+            level      TEXT NOT NULL,
+            message    TEXT NOT NULL,
+            created_at INTEGER,
+            target     TEXT,
+            fields     TEXT)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS downloads (
+            id            INTEGER PRIMARY KEY,
+            downloaded_at INTEGER NOT NULL,
+            feed          TEXT,
+            watch_id      INTEGER,
+            item          TEXT,
+            title         TEXT NOT NULL,
+            url           TEXT)",
         [],
     )?;
     Ok(())
 }
 
-/// Checks if the config directory and database exist; creates them if not.
-/// Opens its own connection since this runs before the shared conn exists.
-pub fn write_settings() {
-    let default_set = Settings::default();
+/// Checks if the database at `set_file` and its directory exist; creates
+/// them if not. Opens its own connection since this runs before the
+/// shared conn exists.
+pub fn write_settings(set_file: &str) -> error::Result<()> {
+    let mut default_set = Settings::default();
+    default_set.log_val = default_log_path(set_file);
     let default_wl = Watchlist::default();
-    let set_file = settings_dir();
-
-    let mut directory = dirs::config_dir().unwrap();
-    directory.push("nyaadle");
-    let directory = String::from(directory.to_str().unwrap());
 
     if !Path::new(&set_file).exists() {
-        println!("nyaadle.db not found. Creating it right now.");
+        status!("{} not found. Creating it right now.", set_file);
 
-        if !Path::new(&directory).exists() {
-            std::fs::create_dir(&directory).expect("Unable to create directory");
+        if let Some(directory) = Path::new(&set_file).parent() {
+            if !directory.as_os_str().is_empty() && !directory.exists() {
+                std::fs::create_dir_all(directory).map_err(Error::fs("create", directory))?;
+            }
         }
 
-        let conn = Connection::open(&set_file).expect("Failed to create database.");
+        let conn = Connection::open(set_file)?;
 
         let db_conn = db_create(&conn);
         let db_ar_write = db_write_dir(&conn, &default_set.ar_key, &default_set.ar_val);
         let db_dl_write = db_write_dir(&conn, &default_set.dl_key, &default_set.dl_val);
         let db_log_write = db_write_dir(&conn, &default_set.log_key, &default_set.log_val);
         let db_whk_write = db_write_dir(&conn, &default_set.whkurl_key, &default_set.whkurl_val);
+        let db_poll_write = db_write_dir(&conn, &default_set.poll_key, &default_set.poll_val);
+        let db_limit_write = db_write_dir(&conn, &default_set.limit_key, &default_set.limit_val);
+        let db_timeout_write =
+            db_write_dir(&conn, &default_set.timeout_key, &default_set.timeout_val);
+        let db_keep_write = db_write_dir(&conn, &default_set.keep_key, &default_set.keep_val);
+        let db_level_write = db_write_dir(&conn, &default_set.level_key, &default_set.level_val);
+        let db_format_write = db_write_dir(&conn, &default_set.format_key, &default_set.format_val);
+        let db_days_write = db_write_dir(&conn, &default_set.days_key, &default_set.days_val);
+        let db_rows_write = db_write_dir(&conn, &default_set.rows_key, &default_set.rows_val);
+        let db_size_write = db_write_dir(&conn, &default_set.size_key, &default_set.size_val);
         let db_ver_write = db_write_dir(&conn, &default_set.ver_key, CURRENT_DB_VERSION);
         let db_feed_write = db_write_feed(&conn, "Default", &default_set.url_val, true);
         let db_wl_write = match get_default_feed_id(&conn) {
@@ -207,46 +753,65 @@ pub fn write_settings() {
         };
 
         match File::create(&default_set.log_val) {
-            Ok(_) => println!("Created log file."),
-            Err(_) => println!("Failed to create log file."),
+            Ok(_) => status!("Created log file."),
+            Err(_) => status!("Failed to create log file."),
         }
 
-        let base_ok = db_conn == Ok(())
-            && db_ar_write == Ok(())
-            && db_dl_write == Ok(())
-            && db_wl_write == Ok(())
-            && db_feed_write == Ok(())
-            && db_ver_write == Ok(())
-            && db_log_write == Ok(());
+        // Keeps the first error, if any.
+        let base = db_conn
+            .and(db_ar_write)
+            .and(db_dl_write)
+            .and(db_wl_write)
+            .and(db_feed_write)
+            .and(db_ver_write)
+            .and(db_log_write)
+            .and(db_poll_write)
+            .and(db_limit_write)
+            .and(db_timeout_write)
+            .and(db_keep_write)
+            .and(db_level_write)
+            .and(db_format_write)
+            .and(db_days_write)
+            .and(db_rows_write)
+            .and(db_size_write);
 
         #[cfg(feature = "discord")]
-        let base_ok = base_ok && db_whk_write == Ok(());
+        let base = base.and(db_whk_write);
         #[cfg(not(feature = "discord"))]
         let _ = db_whk_write;
 
-        if base_ok {
-            println!("nyaadle.db created.");
-            println!("You can change settings by running 'nyaadle set' or 'nyaadle tui'.");
-        } else {
-            println!("Failed to create nyaadle.db");
+        if let Err(e) = base {
+            status!("Failed to create {}", set_file);
+            return Err(e.into());
         }
+        status!("{} created.", set_file);
+        status!("You can change settings by running 'nyaadle set' or 'nyaadle tui'.");
     }
+    Ok(())
 }
 
-/// Ensures the database exists, creating it with defaults if not.
-/// Called at program start before the shared connection is opened.
-pub fn set_check() {
-    if !Path::new(&settings_dir()).exists() {
-        write_settings();
+/// Ensures the database at `path` exists, creating it with defaults if
+/// not. Called at program start before the shared connection is opened.
+pub fn set_check(path: &str) -> error::Result<()> {
+    if !Path::new(path).exists() {
+        write_settings(path)?;
     }
+    Ok(())
 }
 
 // ── Hot-path functions: take &Connection ────────────────────────────────────
 // These are called from the cron-triggered feed_parser path and share the
 // single connection opened in main(). Do not open new connections here.
 
-/// Reads the value for `key` from the directories table.
+/// Returns the effective value for `key`, taking the config file,
+/// environment and command line into account. See `resolve_setting`.
 pub fn get_settings(conn: &Connection, key: &str) -> rusqlite::Result<String> {
+    resolve_setting(conn, key).map(|(value, _)| value)
+}
+
+/// Reads the value for `key` from the directories table only. Used by
+/// editors that write the value back.
+pub fn get_stored_setting(conn: &Connection, key: &str) -> rusqlite::Result<String> {
     let mut stmt = conn.prepare("SELECT path FROM directories WHERE option = :name")?;
     let rows = stmt.query_map(named_params! { ":name": key }, |row| row.get(0))?;
     let mut dir = String::new();
@@ -271,18 +836,96 @@ pub fn get_log(conn: &Connection) -> String {
     get_settings(conn, "log")
         .ok()
         .filter(|s| !s.is_empty())
-        .unwrap_or_else(|| {
-            let mut p = dirs::config_dir().unwrap();
-            p.push("nyaadle");
-            p.push("nyaadle");
-            p.set_extension("log");
-            String::from(p.to_str().unwrap())
-        })
+        .unwrap_or_else(|| default_value(conn, "log").unwrap_or_default())
+}
+
+/// Reads a positive number setting, falling back to
+/// `default` when unset or invalid.
+fn get_number(conn: &Connection, key: &str, default: u64) -> u64 {
+    get_settings(conn, key)
+        .ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(default)
+}
+
+/// Returns the daemon poll interval in minutes.
+pub fn get_poll_interval(conn: &Connection) -> u64 {
+    get_number(conn, "poll-interval", DEFAULT_POLL_INTERVAL)
+}
+
+/// Returns how many feeds may be fetched at the same time.
+pub fn get_fetch_limit(conn: &Connection) -> u64 {
+    get_number(conn, "fetch-limit", DEFAULT_FETCH_LIMIT)
+}
+
+/// Returns the per-feed fetch timeout in seconds.
+pub fn get_fetch_timeout(conn: &Connection) -> u64 {
+    get_number(conn, "fetch-timeout", DEFAULT_FETCH_TIMEOUT)
+}
+
+/// Returns how many automatic backups to keep.
+pub fn get_backup_keep(conn: &Connection) -> u64 {
+    get_number(conn, "backup-keep", DEFAULT_BACKUP_KEEP)
+}
+
+/// Reads a number setting where 0 means no limit, falling back to
+/// `default` when unset or invalid.
+fn get_limit(conn: &Connection, key: &str, default: u64) -> u64 {
+    get_settings(conn, key)
+        .ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or(default)
+}
+
+/// Returns how many days of log entries to keep.
+pub fn get_log_keep_days(conn: &Connection) -> u64 {
+    get_limit(conn, "log-keep-days", DEFAULT_LOG_KEEP_DAYS)
 }
 
-/// Returns the full watchlist from the database.
-pub fn get_wl(conn: &Connection) -> Vec<Watchlist> {
-    read_watch_list(conn).expect("Failed to read watchlist")
+/// Returns how many log entries to keep.
+pub fn get_log_keep_rows(conn: &Connection) -> u64 {
+    get_limit(conn, "log-keep-rows", DEFAULT_LOG_KEEP_ROWS)
+}
+
+/// Returns the size in MB at which the log file is rotated.
+pub fn get_log_max_size(conn: &Connection) -> u64 {
+    get_limit(conn, "log-max-size", DEFAULT_LOG_MAX_SIZE)
+}
+
+/// Returns the configured log level, falling back to info.
+pub fn get_log_level(conn: &Connection) -> LevelFilter {
+    get_settings(conn, "log-level")
+        .ok()
+        .and_then(|s| s.trim().parse::<LevelFilter>().ok())
+        .unwrap_or(LevelFilter::Info)
+}
+
+/// Returns the configured log file format, falling back to text.
+pub fn get_log_format(conn: &Connection) -> LogFormat {
+    get_settings(conn, "log-format")
+        .ok()
+        .and_then(|s| LogFormat::parse(s.trim()))
+        .unwrap_or(LogFormat::Text)
+}
+
+/// Deletes log entries older than `log-keep-days` and all but the newest
+/// `log-keep-rows`. Returns the number removed.
+pub fn prune_logs(conn: &Connection) -> rusqlite::Result<usize> {
+    let mut removed = 0;
+    let days = get_log_keep_days(conn);
+    if days > 0 {
+        let cutoff = OffsetDateTime::now_utc().unix_timestamp() - days as i64 * 86400;
+        removed += conn.execute("DELETE FROM logs WHERE created_at < ?1", params![cutoff])?;
+    }
+    let rows = get_log_keep_rows(conn);
+    if rows > 0 {
+        removed += conn.execute(
+            "DELETE FROM logs WHERE id NOT IN (SELECT id FROM logs ORDER BY id DESC LIMIT ?1)",
+            params![rows as i64],
+        )?;
+    }
+    Ok(removed)
 }
 
 /// Builds a single-entry watchlist vector, used for one-shot parses.
@@ -304,13 +947,25 @@ pub fn read_watch_list(conn: &Connection) -> rusqlite::Result<Vec<Watchlist>> {
 }
 
 pub fn read_feeds(conn: &Connection) -> rusqlite::Result<Vec<Feed>> {
-    let mut stmt = conn.prepare("SELECT id, name, url, is_default FROM feeds")?;
+    let mut stmt = conn.prepare(
+        "SELECT id, name, url, is_default, enabled, interval_minutes, last_checked,
+                etag, last_modified, kind, api_key, categories
+         FROM feeds",
+    )?;
     let stored = stmt.query_map([], |row| {
         Ok(Feed {
             id: row.get(0)?,
             name: row.get(1)?,
             url: row.get(2)?,
             is_default: row.get::<_, i32>(3)? != 0,
+            enabled: row.get::<_, i32>(4)? != 0,
+            interval_minutes: row.get(5)?,
+            last_checked: row.get(6)?,
+            etag: row.get(7)?,
+            last_modified: row.get(8)?,
+            kind: FeedKind::parse(&row.get::<_, String>(9)?).unwrap_or(FeedKind::Rss),
+            api_key: row.get(10)?,
+            categories: row.get(11)?,
         })
     })?;
     let mut feeds = Vec::new();
@@ -428,13 +1083,12 @@ pub fn db_delete_wl(conn: &Connection, wl_key: &str) -> rusqlite::Result<()> {
 
 /// Returns the last-seen item for a given watchlist title.
 pub fn get_tracking(conn: &Connection, key: &str, feed_id: i32) -> rusqlite::Result<String> {
-    let mut stmt = conn.prepare(
-        "SELECT latest FROM item_tracker WHERE item = :name AND feed_id = :feed_id"
-    )?;
-    
-    let mut rows = stmt.query(named_params! { 
-        ":name": key, 
-        ":feed_id": feed_id 
+    let mut stmt =
+        conn.prepare("SELECT latest FROM item_tracker WHERE item = :name AND feed_id = :feed_id")?;
+
+    let mut rows = stmt.query(named_params! {
+        ":name": key,
+        ":feed_id": feed_id
     })?;
 
     if let Some(row) = rows.next()? {
@@ -446,7 +1100,12 @@ pub fn get_tracking(conn: &Connection, key: &str, feed_id: i32) -> rusqlite::Res
 }
 
 /// Updates the last-seen item for a watchlist title, inserting if not present.
-pub fn update_tracking(conn: &Connection, trck_key: &str, trck_val: &str, feed_id: i32) -> rusqlite::Result<()> {
+pub fn update_tracking(
+    conn: &Connection,
+    trck_key: &str,
+    trck_val: &str,
+    feed_id: i32,
+) -> rusqlite::Result<()> {
     conn.execute(
         "INSERT INTO item_tracker (item, latest, feed_id) 
          VALUES (?1, ?2, ?3)
@@ -458,31 +1117,132 @@ pub fn update_tracking(conn: &Connection, trck_key: &str, trck_val: &str, feed_i
 }
 
 /// Sets a key in the directories table from the command line.
-pub fn arg_set(conn: &Connection, key: &str, value: &str) {
+pub fn arg_set(conn: &Connection, key: &str, value: &str) -> error::Result<()> {
     match key {
         "url" => {
-            update_default_feed_url(conn, value).expect("Failed to write to database.");
+            update_default_feed_url(conn, value)?;
             println!("Updated RSS Feed URL to \"{}\"", value);
         }
         "dl-dir" => {
-            update_write_dir(conn, key, value).expect("Failed to write to database.");
+            update_write_dir(conn, key, value)?;
             println!("Updated Download directory to \"{}\"", value);
         }
         "ar-dir" => {
-            update_write_dir(conn, key, value).expect("Failed to write to database.");
+            update_write_dir(conn, key, value)?;
             println!("Updated Archive directory to \"{}\"", value);
         }
         "log" => {
-            update_write_dir(conn, key, value).expect("Failed to write to database.");
+            update_write_dir(conn, key, value)?;
             println!("Updated log file location to \"{}\"", value);
         }
+        "poll-interval" => match value.trim().parse::<u64>() {
+            Ok(m) if m > 0 => {
+                update_write_dir(conn, key, &m.to_string())?;
+                println!("Updated poll interval to {} minutes", m);
+            }
+            _ => {
+                return Err(Error::Config(String::from(
+                    "Poll interval must be a positive number of minutes.",
+                )))
+            }
+        },
+        "fetch-limit" => match value.trim().parse::<u64>() {
+            Ok(n) if n > 0 => {
+                update_write_dir(conn, key, &n.to_string())?;
+                println!("Updated fetch limit to {} feeds at a time", n);
+            }
+            _ => {
+                return Err(Error::Config(String::from(
+                    "Fetch limit must be a positive number.",
+                )))
+            }
+        },
+        "fetch-timeout" => match value.trim().parse::<u64>() {
+            Ok(n) if n > 0 => {
+                update_write_dir(conn, key, &n.to_string())?;
+                println!("Updated fetch timeout to {} seconds", n);
+            }
+            _ => {
+                return Err(Error::Config(String::from(
+                    "Fetch timeout must be a positive number of seconds.",
+                )))
+            }
+        },
+        "log-level" => match value.trim().parse::<LevelFilter>() {
+            Ok(level) => {
+                let level = level.as_str().to_lowercase();
+                update_write_dir(conn, key, &level)?;
+                println!("Updated log level to {}", level);
+            }
+            _ => {
+                return Err(Error::Config(String::from(
+                    "Log level must be one of off, error, warn, info, debug or trace.",
+                )))
+            }
+        },
+        "log-format" => match LogFormat::parse(&value.trim().to_lowercase()) {
+            Some(format) => {
+                update_write_dir(conn, key, format.as_str())?;
+                println!("Updated log format to {}", format.as_str());
+            }
+            None => {
+                return Err(Error::Config(String::from(
+                    "Log format must be text or json.",
+                )))
+            }
+        },
+        "log-keep-days" => match value.trim().parse::<u64>() {
+            Ok(n) => {
+                update_write_dir(conn, key, &n.to_string())?;
+                println!("Updated log retention to {} days", n);
+            }
+            _ => {
+                return Err(Error::Config(String::from(
+                    "Log retention must be a number of days, or 0 to keep everything.",
+                )))
+            }
+        },
+        "log-keep-rows" => match value.trim().parse::<u64>() {
+            Ok(n) => {
+                update_write_dir(conn, key, &n.to_string())?;
+                println!("Updated log retention to {} entries", n);
+            }
+            _ => {
+                return Err(Error::Config(String::from(
+                    "Log retention must be a number of entries, or 0 for no limit.",
+                )))
+            }
+        },
+        "log-max-size" => match value.trim().parse::<u64>() {
+            Ok(n) => {
+                update_write_dir(conn, key, &n.to_string())?;
+                println!("Updated log file size limit to {} MB", n);
+            }
+            _ => {
+                return Err(Error::Config(String::from(
+                    "Log file size must be a number of MB, or 0 to never rotate.",
+                )))
+            }
+        },
+        "backup-keep" => match value.trim().parse::<u64>() {
+            Ok(n) if n > 0 => {
+                update_write_dir(conn, key, &n.to_string())?;
+                println!("Updated automatic backups kept to {}", n);
+            }
+            _ => {
+                return Err(Error::Config(String::from(
+                    "Backups kept must be a positive number.",
+                )))
+            }
+        },
         #[cfg(feature = "discord")]
         "webhk_url" => {
-            update_write_dir(conn, key, value).expect("Failed to write to database.");
+            update_write_dir(conn, key, value)?;
             println!("Updated Discord webhook URL to \"{}\"", value);
         }
-        _ => println!("Unknown key."),
+        _ => return Err(Error::Config(format!("Unknown setting {}.", key))),
     }
+    Ok(())
 }
 
 /// Prints a key's current value from the directories table.
@@ -501,6 +1261,15 @@ pub fn arg_get_set(conn: &Connection, key: &str) {
             "Log File Path: {}",
             get_settings(conn, key).unwrap_or_default()
         ),
+        "poll-interval" => println!("Poll Interval: {} minutes", get_poll_interval(conn)),
+        "fetch-limit" => println!("Fetch Limit: {} feeds", get_fetch_limit(conn)),
+        "fetch-timeout" => println!("Fetch Timeout: {} seconds", get_fetch_timeout(conn)),
+        "backup-keep" => println!("Backups Kept: {}", get_backup_keep(conn)),
+        "log-level" => println!("Log Level: {}", get_log_level(conn).as_str().to_lowercase()),
+        "log-format" => println!("Log Format: {}", get_log_format(conn).as_str()),
+        "log-keep-days" => println!("Log Retention: {} days", get_log_keep_days(conn)),
+        "log-keep-rows" => println!("Log Retention: {} entries", get_log_keep_rows(conn)),
+        "log-max-size" => println!("Log File Size Limit: {} MB", get_log_max_size(conn)),
         "db-ver" => println!(
             "Database version: {}",
             get_settings(conn, key).unwrap_or_default()
@@ -516,198 +1285,95 @@ pub fn arg_get_set(conn: &Connection, key: &str) {
     }
 }
 
-/// Ensures the db-ver entry exists, then migrates up to CURRENT_DB_VERSION
-/// if needed.
-pub fn get_db_ver(conn: &Connection) -> rusqlite::Result<()> {
+/// Ensures the db-ver entry exists, then applies any pending migrations
+/// and makes sure every table exists. Returns the number of migrations
+/// applied.
+pub fn get_db_ver(conn: &Connection) -> rusqlite::Result<usize> {
     let default = Settings::default();
     conn.execute(
         "INSERT OR IGNORE INTO directories (option, path) VALUES (?1, ?2)",
         params![default.ver_key, default.ver_val],
     )?;
-
-    let version = get_settings(conn, "db-ver").unwrap_or_default();
-    if version == CURRENT_DB_VERSION {
-        // Current version, ensure all tables exist
-        db_create(conn)?;
-        // Pre-release "3.0" databases created before multi-feed landed
-        // (e.g. from the earlier logging-refactor session) will already
-        // say db-ver = "3.0" but won't have feed_id on watchlist. Since
-        // 3.0 was never actually released, patch these in place instead
-        // of introducing a new version number.
-        if !watchlist_has_feed_id(conn)? {
-            backfill_feed_id(conn)?;
-        }
-    } else {
-        migrate(conn)?;
-    }
-    Ok(())
+    let applied = migrations::run(conn)?;
+    db_create(conn)?;
+    Ok(applied.len())
 }
 
-/// Runs database migrations, stepping through versions one at a time
-/// until the database is at CURRENT_DB_VERSION or the version is
-/// unrecognized. Add new match arms here as new versions are released.
-fn migrate(conn: &Connection) -> rusqlite::Result<()> {
-    // Create a backup of the live database file right before migrating
-    let db_path = settings_dir();
-    let path = Path::new(&db_path);
-    if path.exists() {
-        let backup_path = format!("{}.bak", db_path);
-        println!(
-            "Backing up database to {} before running migrations...",
-            backup_path
-        );
-        if let Err(e) = std::fs::copy(path, &backup_path) {
-            println!(
-                "Warning: Pre-migration backup failed: {}. Attempting migration anyway.",
-                e
-            );
-        }
-    }
-
-    loop {
-        let version = get_settings(conn, "db-ver").unwrap_or_default();
-        match version.as_str() {
-            "2.0" => {
-                db_create(conn)?;
-                if !watchlist_has_feed_id(conn)? {
-                    backfill_feed_id(conn)?;
-                }
-                update_write_dir(conn, "db-ver", CURRENT_DB_VERSION)?;
-                println!("Migrated database to {}", CURRENT_DB_VERSION);
-            }
-            v if v == CURRENT_DB_VERSION => break,
-            _ => {
-                db_create(conn)?;
-                break;
-            }
-        }
-    }
-    Ok(())
+/// Formats a date-time the same way as the log file timestamps.
+fn format_time(dt: OffsetDateTime) -> String {
+    let format = format_description::parse_borrowed::<3>(
+        "[year]-[month repr:short]-[day] [weekday repr:short] [hour]:[minute]:[second]",
+    )
+    .unwrap();
+    dt.format(&format)
+        .unwrap_or_else(|_| String::from("unknown"))
 }
 
-/// Returns true if the watchlist table already has a feed_id column.
-fn watchlist_has_feed_id(conn: &Connection) -> rusqlite::Result<bool> {
-    let mut stmt = conn.prepare("PRAGMA table_info(watchlist)")?;
-    let cols = stmt.query_map([], |row| row.get::<_, String>(1))?;
-    for col in cols {
-        if col? == "feed_id" {
-            return Ok(true);
-        }
-    }
-    Ok(false)
+/// Reads a timestamp written by `format_time` back as a unix time,
+/// assuming it was written in the current local offset.
+pub fn parse_log_time(timestamp: &str) -> Option<i64> {
+    let format = format_description::parse_borrowed::<3>(
+        "[year]-[month repr:short]-[day] [weekday repr:short] [hour]:[minute]:[second]",
+    )
+    .unwrap();
+    let offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    PrimitiveDateTime::parse(timestamp, &format)
+        .ok()
+        .map(|dt| dt.assume_offset(offset).unix_timestamp())
 }
 
-/// Adds feed_id to an existing watchlist table and backfills every row
-/// to a "Default" feed, seeding that feed from the legacy `url` entry
-/// in `directories` if one doesn't already exist. Used both for the
-/// 2.0 -> 3.0 migration and for patching pre-multi-feed 3.0 dev databases.
-fn backfill_feed_id(conn: &Connection) -> rusqlite::Result<()> {
-    // Ensure the feeds table exists.
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS feeds (
-            id         INTEGER PRIMARY KEY,
-            name       TEXT NOT NULL UNIQUE,
-            url        TEXT NOT NULL,
-            is_default INTEGER NOT NULL DEFAULT 0)",
-        [],
-    )?;
-
-    // Reuse the existing default feed if one is already there (e.g. this
-    // is a re-run), otherwise seed one from the legacy directories.url.
-    let default_feed_id = match get_default_feed_id(conn) {
-        Ok(id) => id,
-        Err(_) => {
-            let old_url = get_settings(conn, "url")
-                .ok()
-                .filter(|s| !s.is_empty())
-                .unwrap_or_else(|| String::from("https://nyaa.si/?page=rss"));
-            db_write_feed(conn, "Default", &old_url, true)?;
-            get_default_feed_id(conn)?
-        }
+/// Formats a unix timestamp in local time for display.
+pub fn format_unix_time(timestamp: i64) -> String {
+    let dt = match OffsetDateTime::from_unix_timestamp(timestamp) {
+        Ok(dt) => dt,
+        Err(_) => return String::from("unknown"),
     };
-
-    // SQLite requires a constant DEFAULT for a NOT NULL ADD COLUMN, so
-    // default to 0 and immediately overwrite with the real id.
-    conn.execute(
-        "ALTER TABLE watchlist ADD COLUMN feed_id INTEGER NOT NULL DEFAULT 0",
-        [],
-    )?;
-    conn.execute(
-        "UPDATE watchlist SET feed_id = ?1",
-        params![default_feed_id],
-    )?;
-
-    // `url` no longer lives in directories.
-    conn.execute("DELETE FROM directories WHERE option = 'url'", [])?;
-
-    let mut stmt = conn.prepare("PRAGMA table_info(item_tracker)")?;
-    let mut rows = stmt.query([])?;
-    let mut has_feed_id = false;
-    
-    while let Some(row) = rows.next()? {
-        let col_name: String = row.get(1)?;
-        if col_name == "feed_id" {
-            has_feed_id = true;
-            break;
-        }
+    match time::UtcOffset::current_local_offset() {
+        Ok(offset) => format_time(dt.to_offset(offset)),
+        Err(_) => format_time(dt),
     }
-
-    // If the legacy table doesn't have the column, migrate it inline
-    if !has_feed_id {
-        // Safe table-recreation pattern for SQLite composite constraints
-        conn.execute("ALTER TABLE item_tracker RENAME TO old_item_tracker;", [])?;
-        
-        conn.execute(
-            "CREATE TABLE item_tracker (
-                id      INTEGER PRIMARY KEY,
-                item    BLOB NOT NULL,
-                latest  BLOB NOT NULL,
-                feed_id INTEGER NOT NULL DEFAULT 0,
-                UNIQUE(item, feed_id)
-            );",
-            [],
-        )?;
-
-        conn.execute(
-            "INSERT INTO item_tracker (id, item, latest, feed_id)
-             SELECT id, item, latest, ?1 FROM old_item_tracker;",
-            params![default_feed_id],
-        )?;
-
-        conn.execute("DROP TABLE old_item_tracker;", [])?;
-    }
-
-    Ok(())
 }
 
-/// Writes a log entry to the logs table.
-pub fn write_log(conn: &Connection, level: &str, message: &str) -> rusqlite::Result<()> {
-    let format = format_description::parse_borrowed::<3>(
-        "[year]-[month repr:short]-[day] [weekday repr:short] [hour]:[minute]:[second]",
-    )
-    .unwrap();
-    let timestamp = OffsetDateTime::now_local()
-        .unwrap_or(OffsetDateTime::now_utc())
-        .format(&format)
-        .unwrap_or_else(|_| String::from("unknown"));
+/// Writes a log entry to the logs table. Called by the database logger
+/// set up in `logging::init`; use the `log` macros instead.
+pub fn write_log(
+    conn: &Connection,
+    level: Level,
+    target: &str,
+    message: &str,
+    fields: Option<&str>,
+) -> rusqlite::Result<()> {
+    let now = OffsetDateTime::now_local().unwrap_or(OffsetDateTime::now_utc());
 
     conn.execute(
-        "INSERT INTO logs (timestamp, level, message) VALUES (?1, ?2, ?3)",
-        params![timestamp, level, message],
+        "INSERT INTO logs (timestamp, level, message, created_at, target, fields)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            format_time(now),
+            level.as_str(),
+            message,
+            now.unix_timestamp(),
+            target,
+            fields
+        ],
     )?;
     Ok(())
 }
 
 /// Reads log entries from thelog table.
 pub fn read_logs(conn: &Connection) -> rusqlite::Result<Vec<Log>> {
-    let mut stmt =
-        conn.prepare("SELECT id, timestamp, level, message FROM logs ORDER BY id DESC LIMIT 500")?;
+    let mut stmt = conn.prepare(
+        "SELECT id, timestamp, level, message, target, fields
+         FROM logs ORDER BY id DESC LIMIT 500",
+    )?;
     let stored = stmt.query_map([], |row| {
         Ok(Log {
             id: row.get(0)?,
             timestamp: row.get(1)?,
             level: row.get(2)?,
             message: row.get(3)?,
+            target: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+            fields: row.get(5)?,
         })
     })?;
     let mut logs = Vec::new();
@@ -716,6 +1382,85 @@ pub fn read_logs(conn: &Connection) -> rusqlite::Result<Vec<Log>> {
     }
     Ok(logs)
 }
+
+/// Adds a download to the history. `feed` and `entry` are `None` for
+/// direct downloads.
+pub fn write_download(
+    conn: &Connection,
+    feed: Option<&str>,
+    entry: Option<&Watchlist>,
+    title: &str,
+    url: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO downloads (downloaded_at, feed, watch_id, item, title, url)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            OffsetDateTime::now_utc().unix_timestamp(),
+            feed,
+            entry.map(|e| e.id),
+            entry.map(|e| e.title.as_str()),
+            title,
+            url
+        ],
+    )?;
+    Ok(())
+}
+
+/// Reads the download history matching `filter`, newest first.
+pub fn read_downloads(
+    conn: &Connection,
+    filter: &HistoryFilter,
+) -> rusqlite::Result<Vec<Download>> {
+    let mut sql = String::from(
+        "SELECT id, downloaded_at, feed, watch_id, item, title, url FROM downloads WHERE 1 = 1",
+    );
+    let mut values: Vec<rusqlite::types::Value> = Vec::new();
+    if let Some(since) = filter.since {
+        sql.push_str(" AND downloaded_at >= ?");
+        values.push(since.into());
+    }
+    if let Some(feed) = &filter.feed {
+        sql.push_str(" AND lower(feed) = lower(?)");
+        values.push(feed.clone().into());
+    }
+    if let Some(item) = &filter.item {
+        match item.trim().parse::<i64>() {
+            Ok(id) => {
+                sql.push_str(" AND watch_id = ?");
+                values.push(id.into());
+            }
+            Err(_) => {
+                sql.push_str(" AND instr(lower(item), lower(?)) > 0");
+                values.push(item.clone().into());
+            }
+        }
+    }
+    if let Some(grep) = &filter.grep {
+        sql.push_str(" AND instr(lower(title), lower(?)) > 0");
+        values.push(grep.clone().into());
+    }
+    sql.push_str(" ORDER BY downloaded_at DESC, id DESC");
+    if let Some(limit) = filter.limit {
+        sql.push_str(" LIMIT ?");
+        values.push((limit as i64).into());
+    }
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(values), |row| {
+        Ok(Download {
+            id: row.get(0)?,
+            downloaded_at: row.get(1)?,
+            feed: row.get(2)?,
+            watch_id: row.get(3)?,
+            item: row.get(4)?,
+            title: row.get(5)?,
+            url: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
 /// Updates the URL of a specific feed by its unique name.
 pub fn update_feed_url(conn: &Connection, name: &str, new_url: &str) -> rusqlite::Result<()> {
     conn.execute(
@@ -734,6 +1479,65 @@ pub fn rename_feed(conn: &Connection, old_name: &str, new_name: &str) -> rusqlit
     Ok(())
 }
 
+/// Sets the source type of a feed along with its Torznab API key and
+/// categories. Passing `None` clears the key or categories.
+pub fn set_feed_source(
+    conn: &Connection,
+    name: &str,
+    kind: FeedKind,
+    api_key: Option<&str>,
+    categories: Option<&str>,
+) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE feeds SET kind = ?1, api_key = ?2, categories = ?3 WHERE name = ?4",
+        params![kind.as_str(), api_key, categories, name],
+    )
+}
+
+/// Enables or disables a feed by its unique name.
+pub fn set_feed_enabled(conn: &Connection, name: &str, enabled: bool) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE feeds SET enabled = ?1 WHERE name = ?2",
+        params![enabled as i32, name],
+    )
+}
+
+/// Sets how many minutes must pass between checks of a feed.
+pub fn set_feed_interval(conn: &Connection, name: &str, minutes: i64) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE feeds SET interval_minutes = ?1 WHERE name = ?2",
+        params![minutes, name],
+    )
+}
+
+/// Records the time a feed was last fetched.
+pub fn update_last_checked(
+    conn: &Connection,
+    feed_id: i32,
+    timestamp: i64,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE feeds SET last_checked = ?1 WHERE id = ?2",
+        params![timestamp, feed_id],
+    )?;
+    Ok(())
+}
+
+/// Stores the cache validators returned with a feed so the next fetch can
+/// be made conditional.
+pub fn update_feed_cache(
+    conn: &Connection,
+    feed_id: i32,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE feeds SET etag = ?1, last_modified = ?2 WHERE id = ?3",
+        params![etag, last_modified, feed_id],
+    )?;
+    Ok(())
+}
+
 /// Shifts the global default feed flag safely to a designated target feed.
 pub fn set_default_feed(conn: &Connection, name: &str) -> rusqlite::Result<()> {
     // Clear out old defaults first
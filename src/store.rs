@@ -0,0 +1,187 @@
+use crate::error::{Error, Result};
+use crate::explain::{self, Explanation};
+use crate::parse::{self, Summary};
+use crate::settings::{self, Download, Feed, FeedKind, HistoryFilter, Watchlist};
+use rusqlite::Connection;
+use std::path::Path;
+
+/// A nyaadle database: its settings, feeds, watch-list and download
+/// history, plus the checks and downloads that run against them.
+///
+/// Each store works with the database its connection points at, so one
+/// process can open several. The futures returned by `check`, `run` and
+/// `download` borrow the connection, so run them on the thread that owns
+/// the store.
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    /// Opens the database at `path`, creating it with the default settings
+    /// if it doesn't exist and upgrading it if it's older than this build.
+    pub fn open(path: impl AsRef<Path>) -> Result<Store> {
+        let path = path
+            .as_ref()
+            .to_str()
+            .ok_or_else(|| Error::Config(String::from("the database path isn't valid UTF-8")))?;
+        settings::set_check(path)?;
+        let conn = settings::open_conn_at(path)?;
+        settings::get_db_ver(&conn)?;
+        Ok(Store { conn })
+    }
+
+    /// Opens the database the command line would use: `NYAADLE_DB` if set,
+    /// otherwise `~/.config/nyaadle/nyaadle.db`.
+    pub fn open_default() -> Result<Store> {
+        Store::open(settings::find_db(None, None).map_err(Error::Config)?)
+    }
+
+    /// Uses an existing connection, such as an in-memory database for
+    /// tests. An empty database gets nyaadle's tables but no feeds or
+    /// watch-list entries.
+    pub fn from_connection(conn: Connection) -> Result<Store> {
+        let tables: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'directories'",
+            [],
+            |row| row.get(0),
+        )?;
+        if tables == 0 {
+            settings::db_create(&conn)?;
+            settings::db_write_dir(&conn, "db-ver", settings::CURRENT_DB_VERSION)?;
+        }
+        settings::get_db_ver(&conn)?;
+        Ok(Store { conn })
+    }
+
+    /// The underlying connection, for anything this API doesn't cover.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Returns a setting such as `dl-dir`, after environment variables and
+    /// any config file have been applied.
+    pub fn setting(&self, key: &str) -> Result<String> {
+        Ok(settings::get_settings(&self.conn, key)?)
+    }
+
+    /// Stores a setting in the database. `key` is one of
+    /// `settings::LAYERED_KEYS`.
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        if !settings::LAYERED_KEYS.contains(&key) {
+            return Err(Error::Config(format!("unknown setting {}", key)));
+        }
+        Ok(settings::update_write_dir(&self.conn, key, value)?)
+    }
+
+    /// Every feed, including disabled ones.
+    pub fn feeds(&self) -> Result<Vec<Feed>> {
+        Ok(settings::read_feeds(&self.conn)?)
+    }
+
+    /// Adds a feed. The first feed added becomes the default one, which
+    /// watch-list entries use unless told otherwise.
+    pub fn add_feed(&self, name: &str, url: &str, kind: FeedKind) -> Result<Feed> {
+        if kind == FeedKind::Search && !url.contains(parse::QUERY_PLACEHOLDER) {
+            return Err(Error::Config(format!(
+                "search feed URLs must contain {}",
+                parse::QUERY_PLACEHOLDER
+            )));
+        }
+        let first = self.feeds()?.is_empty();
+        settings::db_write_feed(&self.conn, name, url, first)?;
+        settings::set_feed_source(&self.conn, name, kind, None, None)?;
+        self.feed(name)
+    }
+
+    /// Removes a feed and the watch-list entries linked to it. If it was
+    /// the default, the oldest remaining feed takes over.
+    pub fn remove_feed(&self, name: &str) -> Result<()> {
+        let feeds = self.feeds()?;
+        let target = self.feed(name)?;
+        let replacement = if target.is_default {
+            feeds
+                .iter()
+                .find(|f| f.id != target.id)
+                .map(|f| f.name.as_str())
+        } else {
+            None
+        };
+        Ok(settings::db_delete_feed(
+            &self.conn,
+            name,
+            replacement,
+            None,
+        )?)
+    }
+
+    fn feed(&self, name: &str) -> Result<Feed> {
+        self.feeds()?
+            .into_iter()
+            .find(|f| f.name == name)
+            .ok_or_else(|| Error::Config(format!("no feed named {}", name)))
+    }
+
+    /// Every watch-list entry.
+    pub fn watch_list(&self) -> Result<Vec<Watchlist>> {
+        Ok(settings::read_watch_list(&self.conn)?)
+    }
+
+    /// Adds a watch-list entry. `option` is a resolution such as `1080`,
+    /// or `parse::NON_VID` to match on the title alone. Without `feed` the
+    /// entry is checked against the default feed.
+    pub fn watch(&self, title: &str, option: &str, feed: Option<&str>) -> Result<Watchlist> {
+        let feed_id = match feed {
+            Some(name) => self.feed(name)?.id,
+            None => self
+                .feeds()?
+                .into_iter()
+                .find(|f| f.is_default)
+                .map(|f| f.id)
+                .ok_or_else(|| Error::Config(String::from("add a feed before watching items")))?,
+        };
+        settings::db_write_wl(&self.conn, title, option, feed_id)?;
+        Ok(Watchlist {
+            id: self.conn.last_insert_rowid() as i32,
+            title: title.to_string(),
+            option: option.to_string(),
+            feed_id,
+        })
+    }
+
+    /// Removes a watch-list entry by ID.
+    pub fn unwatch(&self, id: i32) -> Result<()> {
+        Ok(settings::db_delete_wl(&self.conn, &id.to_string())?)
+    }
+
+    /// Checks every enabled feed, due or not, without downloading anything
+    /// or using up its interval. Every item matching a watch-list entry's
+    /// title is returned in `Summary::found`, with the reason it would be
+    /// skipped, if any.
+    pub async fn check(&self) -> Result<Summary> {
+        parse::feed_parser(&self.conn, true, false, None, None, None).await
+    }
+
+    /// Checks every feed that is due and downloads the new matches, like
+    /// running `nyaadle` on its own. With `force`, items that were already
+    /// downloaded are fetched again.
+    pub async fn run(&self, force: bool) -> Result<Summary> {
+        parse::feed_parser(&self.conn, false, force, None, None, None).await
+    }
+
+    /// Downloads the given URLs to `dl-dir`, opening magnet links with the
+    /// system's default handler.
+    pub async fn download(&self, urls: Vec<String>) -> Result<Summary> {
+        parse::arg_dl(&self.conn, urls).await
+    }
+
+    /// Runs a release title through every watch-list entry and reports
+    /// which of its conditions passed.
+    pub fn explain(&self, title: &str) -> Result<Vec<Explanation>> {
+        explain::explain(&self.conn, title)
+    }
+
+    /// Past downloads, newest first.
+    pub fn history(&self, filter: &HistoryFilter) -> Result<Vec<Download>> {
+        Ok(settings::read_downloads(&self.conn, filter)?)
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::Error;
 use crate::settings;
 use crate::settings::Log;
 use crate::settings::Watchlist;
@@ -7,6 +8,7 @@ use cursive::views::{
 };
 use cursive::Cursive;
 use cursive_table_view::*;
+use rusqlite::Connection;
 use std::cmp::Ordering;
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
@@ -23,6 +25,7 @@ enum WatchColumn {
 enum LogColumn {
     Timestamp,
     Level,
+    Source,
     Message,
 }
 
@@ -33,6 +36,10 @@ enum FeedColumn {
     Name,
     Url,
     Default,
+    Kind,
+    Enabled,
+    Interval,
+    LastChecked,
 }
 
 #[derive(Clone)]
@@ -85,7 +92,15 @@ impl TableViewItem<LogColumn> for Log {
         match column {
             LogColumn::Timestamp => self.timestamp.clone(),
             LogColumn::Level => self.level.clone(),
-            LogColumn::Message => self.message.clone(),
+            LogColumn::Source => self
+                .target
+                .strip_prefix("nyaadle::")
+                .unwrap_or(&self.target)
+                .to_string(),
+            LogColumn::Message => match &self.fields {
+                Some(fields) => format!("{} {}", self.message, fields),
+                None => self.message.clone(),
+            },
         }
     }
     fn cmp(&self, other: &Self, column: LogColumn) -> Ordering
@@ -93,8 +108,12 @@ impl TableViewItem<LogColumn> for Log {
         Self: Sized,
     {
         match column {
-            LogColumn::Timestamp => self.timestamp.cmp(&other.timestamp).then(self.id.cmp(&other.id)),
+            LogColumn::Timestamp => self
+                .timestamp
+                .cmp(&other.timestamp)
+                .then(self.id.cmp(&other.id)),
             LogColumn::Level => self.level.cmp(&other.level),
+            LogColumn::Source => self.target.cmp(&other.target),
             LogColumn::Message => self.message.cmp(&other.message),
         }
     }
@@ -120,6 +139,19 @@ impl TableViewItem<FeedColumn> for settings::Feed {
                     String::from("No")
                 }
             }
+            FeedColumn::Kind => self.kind.as_str().to_string(),
+            FeedColumn::Enabled => {
+                if self.enabled {
+                    String::from("Yes")
+                } else {
+                    String::from("No")
+                }
+            }
+            FeedColumn::Interval => self.interval_minutes.to_string(),
+            FeedColumn::LastChecked => self
+                .last_checked
+                .map(settings::format_unix_time)
+                .unwrap_or_else(|| String::from("Never")),
         }
     }
 
@@ -132,6 +164,10 @@ impl TableViewItem<FeedColumn> for settings::Feed {
             FeedColumn::Name => self.name.cmp(&other.name),
             FeedColumn::Url => self.url.cmp(&other.url),
             FeedColumn::Default => self.is_default.cmp(&other.is_default),
+            FeedColumn::Kind => self.kind.cmp(&other.kind),
+            FeedColumn::Enabled => self.enabled.cmp(&other.enabled),
+            FeedColumn::Interval => self.interval_minutes.cmp(&other.interval_minutes),
+            FeedColumn::LastChecked => self.last_checked.cmp(&other.last_checked),
         }
     }
 }
@@ -194,14 +230,58 @@ pub fn arg_tui(item: &str) {
     siv.run();
 }
 
+/// Shows an error in a dialog, so a failed read or write leaves the
+/// interface running.
+fn show_error(s: &mut Cursive, e: Error) {
+    error!("{}", e);
+    let text = match e.hint() {
+        Some(hint) => format!("{}\n\n{}", e, hint),
+        None => e.to_string(),
+    };
+    s.add_layer(Dialog::info(text).title("Error"));
+}
+
+/// Opens the database, showing an error in a dialog if it can't be.
+fn open_db(s: &mut Cursive) -> Option<Connection> {
+    match settings::open_conn() {
+        Ok(conn) => Some(conn),
+        Err(e) => {
+            show_error(s, e.into());
+            None
+        }
+    }
+}
+
+/// Runs `action` against the database, showing any error in a dialog.
+/// Returns `None` if it failed.
+fn with_db<T>(
+    s: &mut Cursive,
+    action: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+) -> Option<T> {
+    let conn = open_db(s)?;
+    match action(&conn) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            show_error(s, e.into());
+            None
+        }
+    }
+}
+
 /// The Watch-list Editor TUI
 fn wle_tui(s: &mut Cursive) {
+    let loaded = with_db(s, |conn| {
+        Ok((
+            settings::read_watch_list(conn)?,
+            settings::read_feeds(conn)?,
+        ))
+    });
+    let (items, feeds) = match loaded {
+        Some(loaded) => loaded,
+        None => return,
+    };
     s.pop_layer();
 
-    let conn = settings::open_conn().expect("Failed to open database.");
-    let items = settings::read_watch_list(&conn).expect("Failed to unpack vectors");
-    let feeds = settings::read_feeds(&conn).expect("Failed to unpack tracking channels");
-
     let tui_items: Vec<TuiWatchlist> = items
         .into_iter()
         .map(|item| {
@@ -232,7 +312,7 @@ fn wle_tui(s: &mut Cursive) {
         .child(Button::new("Delete", delete_item));
 
     let buttons_right = LinearLayout::horizontal()
-        .child(Button::new("Back", |s| main_tui_layer(s)))
+        .child(Button::new("Back", main_tui_layer))
         .child(DummyView)
         .child(Button::new("Quit", Cursive::quit));
 
@@ -255,8 +335,10 @@ fn add_item(s: &mut Cursive) {
     let edit_title = EditView::new().with_name("title_edit").fixed_width(50);
     let edit_option = EditView::new().with_name("opt_edit").fixed_width(10);
 
-    let conn = settings::open_conn().expect("Failed to open database.");
-    let feeds = settings::read_feeds(&conn).expect("Failed to load tracking channels.");
+    let feeds = match with_db(s, settings::read_feeds) {
+        Some(feeds) => feeds,
+        None => return,
+    };
 
     let mut feed_select = SelectView::<i32>::new();
     let mut default_idx = 0;
@@ -270,23 +352,25 @@ fn add_item(s: &mut Cursive) {
 
     fn ok(s: &mut Cursive, value: String, opt: String, feed_id: i32) {
         if !value.is_empty() && !opt.is_empty() {
-            let conn = settings::open_conn().expect("Failed to open database.");
-
-            let feeds = settings::read_feeds(&conn).expect("Failed to read feeds");
-            let feed_name = feeds
-                .iter()
-                .find(|f| f.id == feed_id)
-                .map(|f| f.name.clone())
-                .unwrap_or_else(|| String::from("Unknown"));
-
             let list = Watchlist {
                 id: 0,
                 title: value,
                 option: opt,
                 feed_id,
             };
-            settings::db_write_wl(&conn, &list.title, &list.option, list.feed_id)
-                .expect("Failed to write into database");
+            let written = with_db(s, |conn| {
+                settings::db_write_wl(conn, &list.title, &list.option, list.feed_id)?;
+                settings::read_feeds(conn)
+            });
+            let feeds = match written {
+                Some(feeds) => feeds,
+                None => return,
+            };
+            let feed_name = feeds
+                .iter()
+                .find(|f| f.id == feed_id)
+                .map(|f| f.name.clone())
+                .unwrap_or_else(|| String::from("Unknown"));
 
             let tui_item = TuiWatchlist {
                 watchlist: list,
@@ -361,8 +445,10 @@ fn edit_item(s: &mut Cursive) {
         .with_name("opt_edit")
         .fixed_width(10);
 
-    let conn = settings::open_conn().expect("Failed to open database.");
-    let feeds = settings::read_feeds(&conn).expect("Failed to load tracking channels.");
+    let feeds = match with_db(s, settings::read_feeds) {
+        Some(feeds) => feeds,
+        None => return,
+    };
 
     let mut feed_select = SelectView::<i32>::new();
     let mut selection_idx = 0;
@@ -376,9 +462,12 @@ fn edit_item(s: &mut Cursive) {
 
     fn ok(s: &mut Cursive, value: &str, opt: String, id: i32, feed_id: i32) {
         if !value.is_empty() && !opt.is_empty() {
-            let conn = settings::open_conn().expect("Failed to open database.");
-            settings::update_wl_with_feed(&conn, value, &opt, feed_id, &id.to_string())
-                .expect("Failed to write to database.");
+            let updated = with_db(s, |conn| {
+                settings::update_wl_with_feed(conn, value, &opt, feed_id, &id.to_string())
+            });
+            if updated.is_none() {
+                return;
+            }
         }
         s.pop_layer();
         wle_tui(s);
@@ -422,11 +511,10 @@ fn delete_item(s: &mut Cursive) {
     match table.item() {
         None => s.add_layer(Dialog::info("No item to delete")),
         Some(index) => {
-            let value = table.borrow_item(index).unwrap();
-            let conn = settings::open_conn().expect("Failed to open database.");
-            settings::db_delete_wl(&conn, &value.watchlist.id.to_string())
-                .expect("Failed to delete item");
-            table.remove_item(index);
+            let id = table.borrow_item(index).unwrap().watchlist.id.to_string();
+            if with_db(s, |conn| settings::db_delete_wl(conn, &id)).is_some() {
+                table.remove_item(index);
+            }
         }
     };
 }
@@ -439,7 +527,16 @@ fn set_tui(s: &mut Cursive) {
         .item("Download directory", String::from("dl-dir"))
         .item("Archive directory", String::from("ar-dir"))
         .item("RSS Feed URL", String::from("url"))
-        .item("Log File Path", String::from("log"));
+        .item("Log File Path", String::from("log"))
+        .item("Daemon Poll Interval", String::from("poll-interval"))
+        .item("Concurrent Feed Fetches", String::from("fetch-limit"))
+        .item("Feed Fetch Timeout", String::from("fetch-timeout"))
+        .item("Automatic Backups Kept", String::from("backup-keep"))
+        .item("Log Level", String::from("log-level"))
+        .item("Log File Format", String::from("log-format"))
+        .item("Days of Log Entries Kept", String::from("log-keep-days"))
+        .item("Log Entries Kept", String::from("log-keep-rows"))
+        .item("Log File Size Limit", String::from("log-max-size"));
 
     #[cfg(feature = "discord")]
     let select = {
@@ -451,10 +548,10 @@ fn set_tui(s: &mut Cursive) {
     let select = select
         .on_submit(on_submit_set)
         .with_name("set_select")
-        .fixed_size((50, 10));
+        .fixed_size((50, 15));
 
     let buttons = LinearLayout::horizontal()
-        .child(Button::new("Back", |s| main_tui_layer(s)))
+        .child(Button::new("Back", main_tui_layer))
         .child(Button::new("Quit", Cursive::quit));
 
     s.add_layer(
@@ -476,6 +573,9 @@ fn on_submit_set(s: &mut Cursive, item: &str) {
         "dl-dir" => dl_edit(s, item),
         "url" => url_edit(s, item),
         "log" => log_edit(s, item),
+        "poll-interval" | "fetch-limit" | "fetch-timeout" | "backup-keep" | "log-keep-days"
+        | "log-keep-rows" | "log-max-size" => number_edit(s, item),
+        "log-level" | "log-format" => choice_edit(s, item),
         #[cfg(feature = "discord")]
         "webhk_url" => webhk_edit(s, item),
         _ => unreachable!("Item not found in list"),
@@ -484,8 +584,10 @@ fn on_submit_set(s: &mut Cursive, item: &str) {
 
 /// Dialog box to edit the Archive Directory
 fn ar_edit(s: &mut Cursive, item: &str) {
-    let conn = settings::open_conn().expect("Failed to open database.");
-    let ar_dir = settings::get_settings(&conn, item).unwrap();
+    let ar_dir = match with_db(s, |conn| settings::get_stored_setting(conn, item)) {
+        Some(value) => value,
+        None => return,
+    };
     let key = String::from(item);
 
     let edit = EditView::new()
@@ -505,11 +607,11 @@ fn ar_edit(s: &mut Cursive, item: &str) {
                     view.get_content().to_string()
                 })
                 .expect("Failed to get value");
-            let conn = settings::open_conn().expect("Failed to open database.");
-            settings::update_write_dir(&conn, &key, &value).expect("Failed to write to database");
-            s.pop_layer();
+            if with_db(s, |conn| settings::update_write_dir(conn, &key, &value)).is_some() {
+                s.pop_layer();
+            }
         })
-        .button("Cancel", |s| set_tui(s))
+        .button("Cancel", set_tui)
         .title("Edit Archive Directory")
         .fixed_size((70, 10)),
     );
@@ -517,8 +619,10 @@ fn ar_edit(s: &mut Cursive, item: &str) {
 
 /// Dialog box to edit the Downloads Directory
 fn dl_edit(s: &mut Cursive, item: &str) {
-    let conn = settings::open_conn().expect("Failed to open database.");
-    let dl_dir = settings::get_settings(&conn, item).unwrap();
+    let dl_dir = match with_db(s, |conn| settings::get_stored_setting(conn, item)) {
+        Some(value) => value,
+        None => return,
+    };
     let key = String::from(item);
 
     let edit = EditView::new()
@@ -538,11 +642,11 @@ fn dl_edit(s: &mut Cursive, item: &str) {
                     view.get_content().to_string()
                 })
                 .expect("Failed to get value");
-            let conn = settings::open_conn().expect("Failed to open database.");
-            settings::update_write_dir(&conn, &key, &value).expect("Failed to write to database");
-            s.pop_layer();
+            if with_db(s, |conn| settings::update_write_dir(conn, &key, &value)).is_some() {
+                s.pop_layer();
+            }
         })
-        .button("Cancel", |s| set_tui(s))
+        .button("Cancel", set_tui)
         .title("Edit Download Directory")
         .fixed_size((70, 10)),
     );
@@ -550,8 +654,10 @@ fn dl_edit(s: &mut Cursive, item: &str) {
 
 /// Dialog box to edit the RSS Feed URL
 fn url_edit(s: &mut Cursive, item: &str) {
-    let conn = settings::open_conn().expect("Failed to open database.");
-    let url = settings::get_settings(&conn, item).unwrap();
+    let url = match with_db(s, |conn| settings::get_stored_setting(conn, item)) {
+        Some(value) => value,
+        None => return,
+    };
     let key = String::from(item);
 
     let edit = EditView::new()
@@ -574,11 +680,11 @@ fn url_edit(s: &mut Cursive, item: &str) {
             if value.is_empty() {
                 value = String::from("https://nyaa.si/?page=rss");
             }
-            let conn = settings::open_conn().expect("Failed to open database.");
-            settings::update_write_dir(&conn, &key, &value).expect("Failed to write to database");
-            s.pop_layer();
+            if with_db(s, |conn| settings::update_write_dir(conn, &key, &value)).is_some() {
+                s.pop_layer();
+            }
         })
-        .button("Cancel", |s| set_tui(s))
+        .button("Cancel", set_tui)
         .title("Edit RSS Feed URL")
         .fixed_size((70, 10)),
     );
@@ -586,8 +692,10 @@ fn url_edit(s: &mut Cursive, item: &str) {
 
 /// Dialog box to edit the Log File Path
 fn log_edit(s: &mut Cursive, item: &str) {
-    let conn = settings::open_conn().expect("Failed to open database.");
-    let log_path = settings::get_settings(&conn, item).unwrap();
+    let log_path = match with_db(s, |conn| settings::get_stored_setting(conn, item)) {
+        Some(value) => value,
+        None => return,
+    };
     let key = String::from(item);
 
     let edit = EditView::new()
@@ -608,45 +716,184 @@ fn log_edit(s: &mut Cursive, item: &str) {
                 })
                 .expect("Failed to get value");
             if value.is_empty() {
-                let mut default = dirs::config_dir().unwrap();
-                default.push("nyaadle");
-                default.push("nyaadle");
-                default.set_extension("log");
-                value = String::from(default.to_str().unwrap());
+                value = settings::default_log_path(settings::settings_dir());
+            }
+            if with_db(s, |conn| settings::update_write_dir(conn, &key, &value)).is_some() {
+                s.pop_layer();
             }
-            let conn = settings::open_conn().expect("Failed to open database.");
-            settings::update_write_dir(&conn, &key, &value).expect("Failed to write to database");
-            s.pop_layer();
         })
-        .button("Cancel", |s| set_tui(s))
+        .button("Cancel", set_tui)
         .title("Edit Log file path")
         .fixed_size((70, 10)),
     );
 }
 
+/// Dialog box to edit a setting that holds a number. Retention and size
+/// limits also take 0 for no limit; everything else must be positive.
+fn number_edit(s: &mut Cursive, item: &str) {
+    let conn = match open_db(s) {
+        Some(conn) => conn,
+        None => return,
+    };
+    let allows_zero = matches!(item, "log-keep-days" | "log-keep-rows" | "log-max-size");
+    let (current, prompt, unit, title) = match item {
+        "poll-interval" => (
+            settings::get_poll_interval(&conn),
+            "Set how many minutes the daemon waits between feed checks",
+            "minutes",
+            "Edit Daemon Poll Interval",
+        ),
+        "fetch-limit" => (
+            settings::get_fetch_limit(&conn),
+            "Set how many feeds are fetched at the same time",
+            "feeds",
+            "Edit Fetch Limit",
+        ),
+        "fetch-timeout" => (
+            settings::get_fetch_timeout(&conn),
+            "Set how many seconds to wait for a single feed",
+            "seconds",
+            "Edit Fetch Timeout",
+        ),
+        "backup-keep" => (
+            settings::get_backup_keep(&conn),
+            "Set how many backups to keep from before migrations",
+            "backups",
+            "Edit Backups Kept",
+        ),
+        "log-keep-days" => (
+            settings::get_log_keep_days(&conn),
+            "Set how many days of log entries to keep (0 keeps everything)",
+            "days",
+            "Edit Log Retention",
+        ),
+        "log-keep-rows" => (
+            settings::get_log_keep_rows(&conn),
+            "Set how many log entries to keep (0 means no limit)",
+            "entries",
+            "Edit Log Retention",
+        ),
+        "log-max-size" => (
+            settings::get_log_max_size(&conn),
+            "Set the size in MB the log file is rotated at (0 never rotates)",
+            "MB",
+            "Edit Log File Size Limit",
+        ),
+        _ => unreachable!("Item not found in list"),
+    };
+    let key = String::from(item);
+
+    let edit = EditView::new()
+        .content(current.to_string())
+        .with_name("number_edit")
+        .fixed_width(70);
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(prompt))
+                .child(edit),
+        )
+        .button("Ok", move |s| {
+            let value = s
+                .call_on_name("number_edit", |view: &mut EditView| {
+                    view.get_content().to_string()
+                })
+                .expect("Failed to get value");
+            match value.trim().parse::<u64>() {
+                Ok(n) if n > 0 || allows_zero => {
+                    let n = n.to_string();
+                    if with_db(s, |conn| settings::update_write_dir(conn, &key, &n)).is_some() {
+                        s.pop_layer();
+                    }
+                }
+                _ if allows_zero => s.add_layer(Dialog::info(format!(
+                    "Enter a number of {}, or 0 for no limit.",
+                    unit
+                ))),
+                _ => s.add_layer(Dialog::info(format!(
+                    "Enter a positive number of {}.",
+                    unit
+                ))),
+            }
+        })
+        .button("Cancel", set_tui)
+        .title(title)
+        .fixed_size((70, 10)),
+    );
+}
+
+/// Dialog box to pick a setting from a fixed list, such as the log level
+fn choice_edit(s: &mut Cursive, item: &str) {
+    let conn = match open_db(s) {
+        Some(conn) => conn,
+        None => return,
+    };
+    let key = String::from(item);
+
+    const LEVELS: &[&str] = &["off", "error", "warn", "info", "debug", "trace"];
+    const FORMATS: &[&str] = &["text", "json"];
+    let (prompt, title, choices, current) = match item {
+        "log-level" => (
+            "Select the lowest level to log",
+            "Edit Log Level",
+            LEVELS,
+            settings::get_log_level(&conn).as_str().to_lowercase(),
+        ),
+        "log-format" => (
+            "Select how the log file is written",
+            "Edit Log File Format",
+            FORMATS,
+            settings::get_log_format(&conn).as_str().to_string(),
+        ),
+        _ => unreachable!("Item not found in list"),
+    };
+
+    let mut select = SelectView::<String>::new();
+    for choice in choices {
+        select.add_item(*choice, String::from(*choice));
+    }
+    select.set_selection(choices.iter().position(|c| *c == current).unwrap_or(0));
+
+    s.add_layer(
+        Dialog::around(LinearLayout::vertical().child(TextView::new(prompt)).child(
+            select.on_submit(move |s, choice: &str| {
+                if with_db(s, |conn| settings::update_write_dir(conn, &key, choice)).is_some() {
+                    s.pop_layer();
+                }
+            }),
+        ))
+        .button("Cancel", set_tui)
+        .title(title)
+        .fixed_size((70, 12)),
+    );
+}
+
 /// The Log Viewer TUI
 fn log_tui(s: &mut Cursive) {
+    let items = match with_db(s, settings::read_logs) {
+        Some(items) => items,
+        None => return,
+    };
     s.pop_layer();
 
-    let conn = settings::open_conn().expect("Failed to open database.");
-    let items = settings::read_logs(&conn).expect("Failed to read logs.");
-
     let mut table = TableView::<Log, LogColumn>::new()
         .column(LogColumn::Timestamp, "Timestamp", |c| c.width(25))
         .column(LogColumn::Level, "Level", |c| c.width(8))
+        .column(LogColumn::Source, "Source", |c| c.width(10))
         .column(LogColumn::Message, "Message", |c| c.width(55))
         .default_column(LogColumn::Timestamp);
 
     table.set_items(items);
 
     let buttons = LinearLayout::horizontal()
-        .child(Button::new("Back", |s| main_tui_layer(s)))
+        .child(Button::new("Back", main_tui_layer))
         .child(Button::new("Quit", Cursive::quit));
 
     s.add_layer(
         Dialog::around(
             LinearLayout::vertical()
-                .child(table.with_name("log-view").min_size((88, 18)))
+                .child(table.with_name("log-view").min_size((100, 18)))
                 .child(buttons),
         )
         .title("Log Viewer"),
@@ -655,8 +902,10 @@ fn log_tui(s: &mut Cursive) {
 
 #[cfg(feature = "discord")]
 fn webhk_edit(s: &mut Cursive, item: &str) {
-    let conn = settings::open_conn().expect("Failed to open database.");
-    let webhk_url = settings::get_settings(&conn, item).unwrap();
+    let webhk_url = match with_db(s, |conn| settings::get_stored_setting(conn, item)) {
+        Some(value) => value,
+        None => return,
+    };
     let key = String::from(item);
 
     let edit = EditView::new()
@@ -676,11 +925,11 @@ fn webhk_edit(s: &mut Cursive, item: &str) {
                     view.get_content().to_string()
                 })
                 .expect("Failed to get value");
-            let conn = settings::open_conn().expect("Failed to open database.");
-            settings::update_write_dir(&conn, &key, &value).expect("Failed to write to database");
-            s.pop_layer();
+            if with_db(s, |conn| settings::update_write_dir(conn, &key, &value)).is_some() {
+                s.pop_layer();
+            }
         })
-        .button("Cancel", |s| set_tui(s))
+        .button("Cancel", set_tui)
         .title("Edit Discord Webhook URL")
         .fixed_size((70, 10)),
     );
@@ -688,16 +937,21 @@ fn webhk_edit(s: &mut Cursive, item: &str) {
 
 /// The Feeds Editor TUI view layer
 fn fds_tui(s: &mut Cursive) {
+    let items = match with_db(s, settings::read_feeds) {
+        Some(items) => items,
+        None => return,
+    };
     s.pop_layer();
 
-    let conn = settings::open_conn().expect("Failed to open database.");
-    let items = settings::read_feeds(&conn).expect("Failed to unpack feeds");
-
     let mut table = TableView::<settings::Feed, FeedColumn>::new()
         .column(FeedColumn::Id, "ID", |c| c.width(5))
         .column(FeedColumn::Name, "Feed Name", |c| c.width(20))
         .column(FeedColumn::Url, "URL", |c| c.width(40))
         .column(FeedColumn::Default, "Def", |c| c.width(5))
+        .column(FeedColumn::Kind, "Type", |c| c.width(8))
+        .column(FeedColumn::Enabled, "On", |c| c.width(5))
+        .column(FeedColumn::Interval, "Every", |c| c.width(7))
+        .column(FeedColumn::LastChecked, "Last Checked", |c| c.width(26))
         .default_column(FeedColumn::Id);
 
     table.set_items(items);
@@ -706,21 +960,22 @@ fn fds_tui(s: &mut Cursive) {
         .child(Button::new("Add", add_feed))
         .child(Button::new("Edit", edit_feed))
         .child(Button::new("Delete", delete_feed))
-        .child(Button::new("Set Default", set_default_feed_ui));
+        .child(Button::new("Set Default", set_default_feed_ui))
+        .child(Button::new("Enable/Disable", toggle_feed_ui));
 
     let buttons_right = LinearLayout::horizontal()
-        .child(Button::new("Back", |s| main_tui_layer(s)))
+        .child(Button::new("Back", main_tui_layer))
         .child(DummyView)
         .child(Button::new("Quit", Cursive::quit));
 
     let button_layer = LinearLayout::horizontal()
-        .child(PaddedView::lrtb(0, 25, 0, 0, buttons_left))
+        .child(PaddedView::lrtb(0, 49, 0, 0, buttons_left))
         .child(buttons_right);
 
     s.add_layer(
         Dialog::around(
             LinearLayout::vertical()
-                .child(table.with_name("feeds-list").min_size((70, 18)))
+                .child(table.with_name("feeds-list").min_size((118, 18)))
                 .child(button_layer),
         )
         .title("Feeds Configuration Editor"),
@@ -734,8 +989,20 @@ fn add_feed(s: &mut Cursive) {
 
     fn ok(s: &mut Cursive, name: String, url: String) {
         if !name.is_empty() && !url.is_empty() {
-            let conn = settings::open_conn().expect("Failed to open database.");
-            settings::db_write_feed(&conn, &name, &url, false).expect("Failed to save channel");
+            let (kind, api_key, categories) = read_feed_source(s);
+            let saved = with_db(s, |conn| {
+                settings::db_write_feed(conn, &name, &url, false)?;
+                settings::set_feed_source(
+                    conn,
+                    &name,
+                    kind,
+                    api_key.as_deref(),
+                    categories.as_deref(),
+                )
+            });
+            if saved.is_none() {
+                return;
+            }
         }
         s.pop_layer();
         fds_tui(s);
@@ -747,7 +1014,8 @@ fn add_feed(s: &mut Cursive) {
                 .child(TextView::new("Feed Name Key:"))
                 .child(edit_name)
                 .child(TextView::new("RSS Stream URL:"))
-                .child(edit_url),
+                .child(edit_url)
+                .child(feed_source_fields(settings::FeedKind::Rss, "", "")),
         )
         .button("Ok", |s| {
             let name = s
@@ -768,6 +1036,59 @@ fn add_feed(s: &mut Cursive) {
     );
 }
 
+/// Builds the feed type, API key and categories fields shared by the add
+/// and edit feed dialogs
+fn feed_source_fields(kind: settings::FeedKind, api_key: &str, categories: &str) -> LinearLayout {
+    let mut kind_select = SelectView::<settings::FeedKind>::new()
+        .item("RSS / Atom", settings::FeedKind::Rss)
+        .item("Torznab", settings::FeedKind::Torznab)
+        .item("Search ({query} in URL)", settings::FeedKind::Search);
+    kind_select.set_selection(match kind {
+        settings::FeedKind::Rss => 0,
+        settings::FeedKind::Torznab => 1,
+        settings::FeedKind::Search => 2,
+    });
+
+    LinearLayout::vertical()
+        .child(TextView::new("Feed Type:"))
+        .child(kind_select.with_name("feed_kind_select"))
+        .child(TextView::new("Torznab API Key:"))
+        .child(
+            EditView::new()
+                .content(api_key)
+                .with_name("feed_api_key_edit")
+                .fixed_width(50),
+        )
+        .child(TextView::new("Torznab Categories (comma-separated):"))
+        .child(
+            EditView::new()
+                .content(categories)
+                .with_name("feed_categories_edit")
+                .fixed_width(30),
+        )
+}
+
+/// Reads back the values entered in the fields built by `feed_source_fields`
+fn read_feed_source(s: &mut Cursive) -> (settings::FeedKind, Option<String>, Option<String>) {
+    let kind = *s
+        .call_on_name(
+            "feed_kind_select",
+            |v: &mut SelectView<settings::FeedKind>| v.selection().unwrap(),
+        )
+        .unwrap();
+    let api_key = s
+        .call_on_name("feed_api_key_edit", |v: &mut EditView| {
+            v.get_content().to_string()
+        })
+        .filter(|k| !k.is_empty());
+    let categories = s
+        .call_on_name("feed_categories_edit", |v: &mut EditView| {
+            v.get_content().to_string()
+        })
+        .filter(|c| !c.is_empty());
+    (kind, api_key, categories)
+}
+
 /// Edits an existing tracking channel's name or endpoint reference
 fn edit_feed(s: &mut Cursive) {
     let table = s
@@ -790,16 +1111,40 @@ fn edit_feed(s: &mut Cursive) {
         .content(&feed.url)
         .with_name("feed_url_edit")
         .fixed_width(50);
+    let edit_interval = EditView::new()
+        .content(feed.interval_minutes.to_string())
+        .with_name("feed_interval_edit")
+        .fixed_width(10);
 
-    fn ok(s: &mut Cursive, old_name: String, new_name: String, url: String) {
+    fn ok(s: &mut Cursive, old_name: String, new_name: String, url: String, interval: String) {
+        let interval = match interval.trim().parse::<i64>() {
+            Ok(m) if m >= 0 => m,
+            _ => {
+                s.add_layer(Dialog::info(
+                    "Interval must be 0 or a positive number of minutes.",
+                ));
+                return;
+            }
+        };
         if !new_name.is_empty() && !url.is_empty() {
-            let conn = settings::open_conn().expect("Failed to open database.");
-            if old_name != new_name {
-                settings::rename_feed(&conn, &old_name, &new_name)
-                    .expect("Failed to execute rename sequence");
+            let (kind, api_key, categories) = read_feed_source(s);
+            let saved = with_db(s, |conn| {
+                if old_name != new_name {
+                    settings::rename_feed(conn, &old_name, &new_name)?;
+                }
+                settings::update_feed_url(conn, &new_name, &url)?;
+                settings::set_feed_interval(conn, &new_name, interval)?;
+                settings::set_feed_source(
+                    conn,
+                    &new_name,
+                    kind,
+                    api_key.as_deref(),
+                    categories.as_deref(),
+                )
+            });
+            if saved.is_none() {
+                return;
             }
-            settings::update_feed_url(&conn, &new_name, &url)
-                .expect("Failed to update path routing");
         }
         s.pop_layer();
         fds_tui(s);
@@ -811,7 +1156,14 @@ fn edit_feed(s: &mut Cursive) {
                 .child(TextView::new("Feed Name:"))
                 .child(edit_name)
                 .child(TextView::new("RSS Stream URL:"))
-                .child(edit_url),
+                .child(edit_url)
+                .child(TextView::new("Check Interval (minutes, 0 = every run):"))
+                .child(edit_interval)
+                .child(feed_source_fields(
+                    feed.kind,
+                    feed.api_key.as_deref().unwrap_or(""),
+                    feed.categories.as_deref().unwrap_or(""),
+                )),
         )
         .button("Ok", move |s| {
             let name = s
@@ -824,7 +1176,12 @@ fn edit_feed(s: &mut Cursive) {
                     v.get_content().to_string()
                 })
                 .unwrap();
-            ok(s, feed.name.clone(), name, url);
+            let interval = s
+                .call_on_name("feed_interval_edit", |v: &mut EditView| {
+                    v.get_content().to_string()
+                })
+                .unwrap();
+            ok(s, feed.name.clone(), name, url, interval);
         })
         .button("Cancel", |s| {
             s.pop_layer();
@@ -834,6 +1191,23 @@ fn edit_feed(s: &mut Cursive) {
 
 /// Manually assigns the global fallback default target channel
 fn set_default_feed_ui(s: &mut Cursive) {
+    let table = s
+        .find_name::<TableView<settings::Feed, FeedColumn>>("feeds-list")
+        .unwrap();
+    match table.item() {
+        None => s.add_layer(Dialog::info("Please highlight a feed entry first.")),
+        Some(idx) => {
+            let name = table.borrow_item(idx).unwrap().name.clone();
+            if with_db(s, |conn| settings::set_default_feed(conn, &name)).is_some() {
+                s.pop_layer();
+                fds_tui(s);
+            }
+        }
+    }
+}
+
+/// Flips whether the highlighted feed is checked on each run
+fn toggle_feed_ui(s: &mut Cursive) {
     let table = s
         .find_name::<TableView<settings::Feed, FeedColumn>>("feeds-list")
         .unwrap();
@@ -841,11 +1215,11 @@ fn set_default_feed_ui(s: &mut Cursive) {
         None => s.add_layer(Dialog::info("Please highlight a feed entry first.")),
         Some(idx) => {
             let feed = table.borrow_item(idx).unwrap();
-            let conn = settings::open_conn().expect("Failed to open database.");
-            settings::set_default_feed(&conn, &feed.name)
-                .expect("Failed to reallocate system default status");
-            s.pop_layer();
-            fds_tui(s);
+            let (name, enabled) = (feed.name.clone(), !feed.enabled);
+            if with_db(s, |conn| settings::set_feed_enabled(conn, &name, enabled)).is_some() {
+                s.pop_layer();
+                fds_tui(s);
+            }
         }
     }
 }
@@ -864,8 +1238,10 @@ fn delete_feed(s: &mut Cursive) {
     };
     let target_feed = table.borrow_item(index).unwrap().clone();
 
-    let conn = settings::open_conn().expect("Failed to open database.");
-    let feeds = settings::read_feeds(&conn).expect("Failed to unpack database entities.");
+    let feeds = match with_db(s, settings::read_feeds) {
+        Some(feeds) => feeds,
+        None => return,
+    };
 
     if feeds.len() <= 1 {
         s.add_layer(Dialog::info(
@@ -909,8 +1285,10 @@ fn prompt_tui_watchlist_reassignment(
     target_feed: settings::Feed,
     replacement_default: Option<String>,
 ) {
-    let conn = settings::open_conn().expect("Failed to open database.");
-    let feeds = settings::read_feeds(&conn).expect("Failed to clear tracking frames.");
+    let feeds = match with_db(s, settings::read_feeds) {
+        Some(feeds) => feeds,
+        None => return,
+    };
 
     let mut reassign_select = SelectView::<Option<String>>::new();
     reassign_select.add_item("[Drop / Purge All Associated Watchlist Items]", None);
@@ -939,17 +1317,18 @@ fn prompt_tui_watchlist_reassignment(
                 )
                 .unwrap();
 
-            let conn = settings::open_conn().expect("Failed to open database.");
-            settings::db_delete_feed(
-                &conn,
-                &target_feed.name,
-                replacement_default.as_deref(),
-                reassign_name.as_deref(),
-            )
-            .expect("Failed to drop database dependencies properly.");
-
-            s.pop_layer();
-            fds_tui(s);
+            let deleted = with_db(s, |conn| {
+                settings::db_delete_feed(
+                    conn,
+                    &target_feed.name,
+                    replacement_default.as_deref(),
+                    reassign_name.as_deref(),
+                )
+            });
+            if deleted.is_some() {
+                s.pop_layer();
+                fds_tui(s);
+            }
         })
         .button("Cancel", |s| {
             s.pop_layer();
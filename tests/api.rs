@@ -0,0 +1,232 @@
+use nyaadle::{Error, FeedKind, Skip, Store};
+use rusqlite::Connection;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+  <channel>
+    <title>Local</title>
+    <link>http://127.0.0.1/</link>
+    <description>stub</description>
+    <item>
+      <title>[Sub] Frieren - 07 [1080p].mkv</title>
+      <link>http://127.0.0.1/dl/7.torrent</link>
+      <guid>frieren-07-1080</guid>
+    </item>
+    <item>
+      <title>[Sub] Frieren - 07 [720p].mkv</title>
+      <link>http://127.0.0.1/dl/7-720.torrent</link>
+      <guid>frieren-07-720</guid>
+    </item>
+    <item>
+      <title>Spy x Family v01 (Digital)</title>
+      <link>http://127.0.0.1/dl/v01.torrent</link>
+      <guid>spy-v01</guid>
+    </item>
+  </channel>
+</rss>"#;
+
+/// Serves `FEED` to a single request and returns its URL.
+async fn serve_feed() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/feed.xml", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 2048];
+        let _ = socket.read(&mut buf).await.unwrap();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/rss+xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            FEED.len(),
+            FEED
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+    });
+    url
+}
+
+/// Serves `FEED` with an ETag, answering a request that already has it with
+/// 304 Not Modified, and returns its URL.
+async fn serve_cached_feed() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/feed.xml", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 2048];
+            let n = socket.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+            let response = if request.contains("if-none-match: \"v1\"") {
+                String::from(
+                    "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n",
+                )
+            } else {
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/rss+xml\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    FEED.len(),
+                    FEED
+                )
+            };
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    url
+}
+
+fn store() -> Store {
+    Store::from_connection(Connection::open_in_memory().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn check_returns_the_matches() {
+    let store = store();
+    let url = serve_feed().await;
+    store.add_feed("Local", &url, FeedKind::Rss).unwrap();
+    let frieren = store.watch("Frieren", "1080", None).unwrap();
+    store
+        .watch("Spy x Family", nyaadle::NON_VID, Some("Local"))
+        .unwrap();
+
+    let summary = store.check().await.unwrap();
+    assert_eq!(summary.feeds, 1);
+    assert_eq!(summary.failed, 0);
+    assert_eq!(summary.downloaded, 0);
+
+    let plan: Vec<(&str, Option<Skip>)> = summary
+        .found
+        .iter()
+        .map(|m| (m.title.as_str(), m.skip))
+        .collect();
+    assert_eq!(
+        plan,
+        vec![
+            ("[Sub] Frieren - 07 [1080p].mkv", None),
+            ("[Sub] Frieren - 07 [720p].mkv", Some(Skip::OptionMismatch)),
+            ("Spy x Family v01 (Digital)", None),
+        ]
+    );
+    assert_eq!(summary.found[0].watch_id, frieren.id);
+    assert_eq!(summary.found[0].feed, "Local");
+    assert!(summary.found[0]
+        .destination
+        .as_deref()
+        .unwrap()
+        .ends_with("/7.torrent"));
+    assert!(store.history(&Default::default()).unwrap().is_empty());
+    // A dry run doesn't use up the feed's interval.
+    assert!(store.feeds().unwrap()[0].last_checked.is_none());
+}
+
+#[tokio::test]
+async fn run_checks_the_feed_without_a_lock_file() {
+    // An in-memory store has no database file to put a run lock next to,
+    // so it runs without touching the default database's lock.
+    let store = store();
+    let url = serve_feed().await;
+    store.add_feed("Local", &url, FeedKind::Rss).unwrap();
+    store.watch("Dungeon Meshi", "1080", Some("Local")).unwrap();
+
+    let summary = store.run(false).await.unwrap();
+    assert_eq!(summary.feeds, 1);
+    assert_eq!(summary.failed, 0);
+    assert_eq!(summary.downloaded, 0);
+    assert!(store.feeds().unwrap()[0].last_checked.is_some());
+}
+
+#[tokio::test]
+async fn check_plans_feeds_a_run_just_checked() {
+    let store = store();
+    let url = serve_cached_feed().await;
+    store.add_feed("Local", &url, FeedKind::Rss).unwrap();
+    store
+        .connection()
+        .execute("UPDATE feeds SET interval_minutes = 60", [])
+        .unwrap();
+    store.watch("Frieren", "480", Some("Local")).unwrap();
+
+    // The run caches the feed's ETag and starts its interval.
+    let summary = store.run(false).await.unwrap();
+    assert_eq!(summary.downloaded, 0);
+    assert_eq!(store.feeds().unwrap()[0].etag.as_deref(), Some("\"v1\""));
+
+    // The feed isn't due and would answer 304, but a check still plans it.
+    let summary = store.check().await.unwrap();
+    assert_eq!(summary.feeds, 1);
+    let skips: Vec<Option<Skip>> = summary.found.iter().map(|m| m.skip).collect();
+    assert_eq!(skips, vec![Some(Skip::OptionMismatch); 2]);
+}
+
+#[test]
+fn stores_keep_to_their_own_database_files() {
+    let dir = std::env::temp_dir().join(format!("nyaadle-stores-{}", std::process::id()));
+    let first = Store::open(dir.join("first.db")).unwrap();
+    let second = Store::open(dir.join("second.db")).unwrap();
+    first
+        .add_feed("Nyaa", "https://nyaa.si/?page=rss", FeedKind::Rss)
+        .unwrap();
+    let names = |store: &Store| -> Vec<String> {
+        store.feeds().unwrap().into_iter().map(|f| f.name).collect()
+    };
+    assert_eq!(names(&first), vec!["Default", "Nyaa"]);
+    assert_eq!(names(&second), vec!["Default"]);
+    drop(second);
+
+    // Migrating a database through a connection backs it up next to that
+    // database, not the default one.
+    let conn = Connection::open(dir.join("second.db")).unwrap();
+    conn.execute("DELETE FROM schema_migrations WHERE version = 8", [])
+        .unwrap();
+    Store::from_connection(conn).unwrap();
+    let backups: Vec<String> = std::fs::read_dir(dir.join("backups"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(backups.len(), 1);
+    assert!(backups[0].starts_with("second-pre-migration-"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn feeds_and_the_watch_list_can_be_managed() {
+    let store = store();
+    assert!(matches!(
+        store.watch("Frieren", "1080", None),
+        Err(Error::Config(_))
+    ));
+
+    store
+        .add_feed("Nyaa", "https://nyaa.si/?page=rss", FeedKind::Rss)
+        .unwrap();
+    let search = store
+        .add_feed(
+            "Search",
+            "https://nyaa.si/?page=rss&q={query}",
+            FeedKind::Search,
+        )
+        .unwrap();
+    assert_eq!(search.kind, FeedKind::Search);
+    assert!(store
+        .add_feed("Broken", "https://nyaa.si/?page=rss", FeedKind::Search)
+        .is_err());
+
+    let entry = store.watch("Frieren", "1080", Some("Search")).unwrap();
+    assert_eq!(entry.feed_id, search.id);
+    store.watch("Dungeon Meshi", "1080", Some("Nyaa")).unwrap();
+    store.unwatch(entry.id).unwrap();
+    let titles: Vec<String> = store
+        .watch_list()
+        .unwrap()
+        .into_iter()
+        .map(|w| w.title)
+        .collect();
+    assert_eq!(titles, vec!["Dungeon Meshi"]);
+
+    store.remove_feed("Nyaa").unwrap();
+    let feeds = store.feeds().unwrap();
+    assert_eq!(feeds.len(), 1);
+    assert!(feeds[0].is_default);
+    assert!(store.watch_list().unwrap().is_empty());
+
+    store.set_setting("dl-dir", "/srv/downloads").unwrap();
+    assert!(store.set_setting("colour", "blue").is_err());
+}
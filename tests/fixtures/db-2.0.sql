@@ -0,0 +1,25 @@
+-- Schema and sample data as written by nyaadle 2.x.
+CREATE TABLE directories (
+    option TEXT PRIMARY KEY,
+    path   TEXT NOT NULL UNIQUE);
+INSERT INTO directories (option, path) VALUES
+    ('dl-dir', '/home/user/Downloads'),
+    ('ar-dir', '/home/user/Downloads/archive'),
+    ('log', '/home/user/.config/nyaadle/nyaadle.log'),
+    ('url', 'https://nyaa.si/?page=rss&c=1_2&f=0'),
+    ('db-ver', '2.0');
+
+CREATE TABLE watchlist (
+    id     INTEGER PRIMARY KEY,
+    name   TEXT NOT NULL,
+    option TEXT NOT NULL);
+INSERT INTO watchlist (name, option) VALUES
+    ('Frieren', '1080p'),
+    ('Dungeon Meshi', '720p');
+
+CREATE TABLE item_tracker (
+    id     INTEGER PRIMARY KEY,
+    item   BLOB NOT NULL UNIQUE,
+    latest BLOB NOT NULL);
+INSERT INTO item_tracker (item, latest) VALUES
+    ('Frieren', '[SubsPlease] Frieren - 12 (1080p).mkv');
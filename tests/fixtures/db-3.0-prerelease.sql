@@ -0,0 +1,28 @@
+-- Schema written by 3.0 development builds: db-ver already says 3.0,
+-- but the feed URL is still a setting and nothing is linked to a feed.
+CREATE TABLE directories (
+    option TEXT PRIMARY KEY,
+    path   TEXT NOT NULL UNIQUE);
+INSERT INTO directories (option, path) VALUES
+    ('dl-dir', '/home/user/Downloads'),
+    ('ar-dir', '/home/user/Downloads/archive'),
+    ('log', '/home/user/.config/nyaadle/nyaadle.log'),
+    ('url', 'https://nyaa.si/?page=rss&c=3_0&f=0'),
+    ('db-ver', '3.0');
+
+CREATE TABLE watchlist (
+    id     INTEGER PRIMARY KEY,
+    name   TEXT NOT NULL,
+    option TEXT NOT NULL);
+INSERT INTO watchlist (name, option) VALUES ('Spy x Family', 'v01');
+
+CREATE TABLE item_tracker (
+    id     INTEGER PRIMARY KEY,
+    item   BLOB NOT NULL UNIQUE,
+    latest BLOB NOT NULL);
+
+CREATE TABLE logs (
+    id        INTEGER PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    level     TEXT NOT NULL,
+    message   TEXT NOT NULL);
@@ -0,0 +1,47 @@
+-- Schema and sample data as written by nyaadle 3.0.
+CREATE TABLE directories (
+    option TEXT PRIMARY KEY,
+    path   TEXT NOT NULL UNIQUE);
+INSERT INTO directories (option, path) VALUES
+    ('dl-dir', '/home/user/Downloads'),
+    ('ar-dir', '/home/user/Downloads/archive'),
+    ('log', '/home/user/.config/nyaadle/nyaadle.log'),
+    ('webhk_url', ''),
+    ('db-ver', '3.0');
+
+CREATE TABLE feeds (
+    id         INTEGER PRIMARY KEY,
+    name       TEXT NOT NULL UNIQUE,
+    url        TEXT NOT NULL,
+    is_default INTEGER NOT NULL DEFAULT 0);
+INSERT INTO feeds (id, name, url, is_default) VALUES
+    (1, 'Default', 'https://nyaa.si/?page=rss', 1),
+    (3, 'Books', 'https://nyaa.si/?page=rss&c=3_0&f=0', 0);
+
+CREATE TABLE watchlist (
+    id      INTEGER PRIMARY KEY,
+    name    TEXT NOT NULL,
+    option  TEXT NOT NULL,
+    feed_id INTEGER NOT NULL REFERENCES feeds(id),
+    UNIQUE(name, feed_id));
+INSERT INTO watchlist (name, option, feed_id) VALUES
+    ('Frieren', '1080p', 1),
+    ('Spy x Family', 'v01', 3);
+
+CREATE TABLE item_tracker (
+    id      INTEGER PRIMARY KEY,
+    item    BLOB NOT NULL,
+    latest  BLOB NOT NULL,
+    feed_id INTEGER NOT NULL DEFAULT 0,
+    UNIQUE(item, feed_id));
+INSERT INTO item_tracker (item, latest, feed_id) VALUES
+    ('Spy x Family', 'Spy x Family v01.cbz', 3);
+
+CREATE TABLE logs (
+    id        INTEGER PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    level     TEXT NOT NULL,
+    message   TEXT NOT NULL);
+INSERT INTO logs (timestamp, level, message) VALUES
+    ('2024-Mar-05 Tue 21:14:03', 'INFO', 'Downloaded Spy x Family v01.cbz'),
+    ('unknown', 'WARN', 'Watch-list not found.');